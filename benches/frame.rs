@@ -0,0 +1,64 @@
+// Time a clock frame and count the bytes it copies, drawing into a
+// separate `Vec` and uploading it whole as before, against drawing straight
+// into a pair of buffers and restoring only what the hands covered
+
+use sometime::{Background, Canvas, Config, Contents, SIDE};
+use std::time::{Duration, Instant};
+
+const FRAMES: u32 = 600;
+
+fn main() {
+    let frame_bytes = (SIDE * SIDE * 4) as usize;
+    let mut canvas = Canvas::new(SIDE, &Config::default());
+
+    // Before: the background copied into `pixel_data`, then all of it into shm
+    let mut pixel_data = vec![0u8; frame_bytes];
+    let mut shm = vec![0u8; frame_bytes];
+    let mut copied = 0;
+    let started = Instant::now();
+    for second in 0..FRAMES {
+        // Nothing kept from the last frame, so all of the background comes back
+        let mut contents = Contents::default();
+        copied += contents.stale_bytes(Background::Clock, &canvas);
+        canvas.clear(&mut pixel_data, &mut contents, Background::Clock);
+        draw(&mut canvas, &mut pixel_data, second);
+        canvas.finish(&mut contents);
+        copied += upload(&pixel_data, &mut shm);
+    }
+    report("before", started.elapsed(), copied);
+
+    // After: drawn in place, the compositor releasing each buffer in turn
+    let mut buffers = [
+        (vec![0u8; frame_bytes], Contents::default()),
+        (vec![0u8; frame_bytes], Contents::default()),
+    ];
+    let mut copied = 0;
+    let started = Instant::now();
+    for second in 0..FRAMES {
+        let (pixels, contents) = &mut buffers[second as usize % 2];
+        copied += contents.stale_bytes(Background::Clock, &canvas);
+        canvas.clear(pixels, contents, Background::Clock);
+        draw(&mut canvas, pixels, second);
+        canvas.finish(contents);
+    }
+    report("after", started.elapsed(), copied);
+}
+
+fn draw(canvas: &mut Canvas, pixels: &mut [u8], second: u32) {
+    canvas.draw_clock_hands(pixels, 10, 10 + second / 60, second % 60);
+}
+
+// Copy a whole frame into shm, counting the bytes
+fn upload(pixels: &[u8], shm: &mut [u8]) -> usize {
+    shm.copy_from_slice(pixels);
+    pixels.len()
+}
+
+fn report(name: &str, elapsed: Duration, copied: usize) {
+    println!(
+        "{:>6}: {:>8.1} µs/frame, {:>8} bytes copied/frame",
+        name,
+        elapsed.as_secs_f64() * 1e6 / FRAMES as f64,
+        copied / FRAMES as usize,
+    );
+}
@@ -0,0 +1,328 @@
+use anyhow::Context;
+use chrono::{Datelike, NaiveDateTime, NaiveTime, TimeDelta, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
+use std::path::PathBuf;
+use wayland_client::QueueHandle;
+
+use crate::{
+    Event, Sometime, State, View, config, hook,
+    ipc::{Payload, Reply},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alarm {
+    pub id: u32,
+    pub time: NaiveTime,
+    // Every day when empty
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    pub label: Option<String>,
+}
+
+// The layout of `alarms.toml`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Alarms {
+    #[serde(default, rename = "alarm")]
+    alarms: Vec<Alarm>,
+}
+
+impl Alarm {
+    // The first time it goes off strictly after `now`, in local time
+    pub fn next_after(&self, now: NaiveDateTime) -> NaiveDateTime {
+        (0..=7)
+            .map(|days| (now.date() + TimeDelta::days(days)).and_time(self.time))
+            .find(|at| *at > now && self.rings_on(at.weekday()))
+            .expect("an alarm rings at least once a week")
+    }
+
+    fn rings_on(&self, weekday: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&weekday)
+    }
+}
+
+impl std::fmt::Display for Alarm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.id, self.time.format("%H:%M"))?;
+
+        if self.days.is_empty() {
+            write!(f, " daily")?;
+        } else {
+            write!(f, " {}", format_days(&self.days))?;
+        }
+
+        if let Some(label) = &self.label {
+            write!(f, " {}", label)?;
+        }
+        Ok(())
+    }
+}
+
+// `mon-fri`, `sat,sun`, `mon,wed-fri` or `daily`, in week order
+pub fn parse_days(s: &str) -> Result<Vec<Weekday>, String> {
+    if s == "daily" {
+        return Ok(Vec::new());
+    }
+
+    let weekday = |name: &str| {
+        name.parse::<Weekday>()
+            .map_err(|_| format!("Unknown day: {}", name))
+    };
+
+    let mut days = Vec::new();
+    for part in s.split(',') {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (weekday(first)?, weekday(last)?),
+            None => (weekday(part)?, weekday(part)?),
+        };
+
+        // Ranges may wrap around the week, as in `fri-mon`
+        let mut day = first;
+        loop {
+            if !days.contains(&day) {
+                days.push(day);
+            }
+            if day == last {
+                break;
+            }
+            day = day.succ();
+        }
+    }
+
+    days.sort_by_key(|day| day.num_days_from_monday());
+    Ok(days)
+}
+
+pub fn format_days(days: &[Weekday]) -> String {
+    days.iter()
+        .map(|day| day.to_string().to_lowercase())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl Sometime {
+    // Pick up the alarms saved by a previous run
+    pub fn load_alarms(&mut self, qh: &QueueHandle<Self>) -> anyhow::Result<()> {
+        let Some(path) = alarms_path() else {
+            return Ok(());
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                let alarms: Alarms = toml::from_str(&contents)
+                    .with_context(|| format!("invalid {}", path.display()))?;
+                self.alarms = alarms.alarms;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).with_context(|| format!("can't read {}", path.display())),
+        }
+
+        self.schedule_alarms(qh);
+        Ok(())
+    }
+
+    pub(crate) fn add_alarm(
+        &mut self,
+        time: NaiveTime,
+        days: Vec<Weekday>,
+        label: Option<String>,
+        qh: &QueueHandle<Self>,
+    ) -> anyhow::Result<Reply> {
+        let id = self.alarms.iter().map(|alarm| alarm.id).max().unwrap_or(0) + 1;
+        let alarm = Alarm {
+            id,
+            time,
+            days,
+            label,
+        };
+        let reply = Reply::State(Payload::Alarms {
+            alarms: vec![alarm.clone()],
+        });
+
+        self.alarms.push(alarm);
+        if let Err(e) = self.save_alarms() {
+            self.alarms.pop();
+            return Err(e);
+        }
+        self.schedule_alarms(qh);
+
+        Ok(reply)
+    }
+
+    pub(crate) fn remove_alarm(
+        &mut self,
+        id: u32,
+        qh: &QueueHandle<Self>,
+    ) -> anyhow::Result<Reply> {
+        let Some(idx) = self.alarms.iter().position(|alarm| alarm.id == id) else {
+            anyhow::bail!("no alarm {}", id);
+        };
+
+        let alarm = self.alarms.remove(idx);
+        if let Err(e) = self.save_alarms() {
+            self.alarms.insert(idx, alarm);
+            return Err(e);
+        }
+        self.schedule_alarms(qh);
+
+        Ok(Reply::Ok)
+    }
+
+    pub(crate) fn list_alarms(&self) -> Payload {
+        Payload::Alarms {
+            alarms: self.alarms.clone(),
+        }
+    }
+
+    // Arm a single timer for whichever alarm rings next, again whenever the
+    // alarms or the wall clock change
+    pub(crate) fn schedule_alarms(&mut self, qh: &QueueHandle<Self>) {
+        if let Some(token) = self.alarm_token.take() {
+            self.loop_handle.remove(token);
+        }
+
+        // Don't ring twice if the timer went off a little early
+        let now = match self.last_rung {
+            Some(last_rung) => self.local_now().max(last_rung),
+            None => self.local_now(),
+        };
+        let Some(at) = self.alarms.iter().map(|alarm| alarm.next_after(now)).min() else {
+            return;
+        };
+
+        let delay = self
+            .local_to_utc(at)
+            .and_then(|at| (at - Utc::now()).to_std().ok())
+            .unwrap_or_default();
+
+        let qh = qh.clone();
+        self.alarm_token = self
+            .loop_handle
+            .insert_source(Timer::from_duration(delay), move |_, _, app| {
+                app.alarm_token = None;
+                app.alarms_ring(at, &qh);
+                TimeoutAction::Drop
+            })
+            .ok();
+    }
+
+    fn alarms_ring(&mut self, at: NaiveDateTime, qh: &QueueHandle<Self>) {
+        let just_before = at - TimeDelta::seconds(1);
+        let ringing: Vec<Alarm> = self
+            .alarms
+            .iter()
+            .filter(|alarm| alarm.next_after(just_before) == at)
+            .cloned()
+            .collect();
+
+        for alarm in &ringing {
+            if let Some(command) = &self.config.alarm.command {
+                let label = alarm.label.as_deref().unwrap_or_default();
+                hook::run(command, &[("SOMETIME_ALARM", label)]);
+            }
+        }
+
+        if let Some(alarm) = ringing.first() {
+            let label = match &alarm.label {
+                Some(label) => label.clone(),
+                None => alarm.time.format("%H:%M").to_string(),
+            };
+            self.ringing = Some(label);
+
+            // Onto the clock whatever's open, or opening it with the local time
+            let before = self.state;
+            if before == State::Sleep {
+                self.tz = None;
+            }
+            self.handle(Event::Show(View::Clock), qh);
+            if self.state == before {
+                self.draw();
+            }
+
+            self.flash();
+        }
+
+        self.last_rung = Some(at);
+        self.schedule_alarms(qh);
+    }
+
+    fn save_alarms(&self) -> anyhow::Result<()> {
+        let Some(path) = alarms_path() else {
+            anyhow::bail!("can't find a state directory to save alarms in");
+        };
+
+        let contents = toml::to_string(&Alarms {
+            alarms: self.alarms.clone(),
+        })?;
+
+        config::write_state(&path, &contents)
+    }
+}
+
+fn alarms_path() -> Option<PathBuf> {
+    config::state_path("alarms.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Alarm, Alarms, parse_days};
+    use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Weekday::*};
+
+    fn at(day: u32, hour: u32, min: u32) -> NaiveDateTime {
+        // 2026-10-12 is a Monday
+        NaiveDate::from_ymd_opt(2026, 10, day)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn days() {
+        assert_eq!(parse_days("mon-fri"), Ok(vec![Mon, Tue, Wed, Thu, Fri]));
+        assert_eq!(parse_days("sun,sat"), Ok(vec![Sat, Sun]));
+        assert_eq!(parse_days("fri-mon"), Ok(vec![Mon, Fri, Sat, Sun]));
+        assert_eq!(parse_days("mon,mon-tue"), Ok(vec![Mon, Tue]));
+        assert_eq!(parse_days("daily"), Ok(vec![]));
+        assert!(parse_days("mon-funday").is_err());
+        assert!(parse_days("").is_err());
+    }
+
+    #[test]
+    fn next_ring() {
+        let weekdays = Alarm {
+            id: 1,
+            time: NaiveTime::from_hms_opt(7, 30, 0).unwrap(),
+            days: vec![Mon, Tue, Wed, Thu, Fri],
+            label: None,
+        };
+
+        // Later today
+        assert_eq!(weekdays.next_after(at(12, 6, 0)), at(12, 7, 30));
+        // Not twice at the same time
+        assert_eq!(weekdays.next_after(at(12, 7, 30)), at(13, 7, 30));
+        // Friday evening skips the weekend
+        assert_eq!(weekdays.next_after(at(16, 20, 0)), at(19, 7, 30));
+
+        let daily = Alarm {
+            days: vec![],
+            ..weekdays
+        };
+        assert_eq!(daily.next_after(at(16, 20, 0)), at(17, 7, 30));
+    }
+
+    #[test]
+    fn state_file_round_trips() {
+        let alarms = Alarms {
+            alarms: vec![Alarm {
+                id: 1,
+                time: NaiveTime::from_hms_opt(7, 30, 0).unwrap(),
+                days: vec![Mon, Fri],
+                label: Some("standup".into()),
+            }],
+        };
+
+        let contents = toml::to_string(&alarms).unwrap();
+        let parsed: Alarms = toml::from_str(&contents).unwrap();
+        assert_eq!(parsed.alarms, alarms.alarms);
+    }
+}
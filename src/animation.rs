@@ -0,0 +1,138 @@
+use smithay_client_toolkit::{
+    reexports::calloop::{
+        RegistrationToken,
+        timer::{TimeoutAction, Timer},
+    },
+    shell::WaylandSurface,
+};
+use std::time::{Duration, Instant};
+use wayland_client::QueueHandle;
+
+use crate::{Event, Sometime, State, config::AnimationStyle};
+
+// How much longer than the animation frame callbacks get before it's
+// finished without them, as when the output is off
+const SLACK: Duration = Duration::from_millis(250);
+
+// Where an animation started from, so turning back halfway stays smooth
+pub(crate) struct Animation {
+    from: f32,
+    started: Instant,
+    fallback: Option<RegistrationToken>,
+}
+
+impl Sometime {
+    // Start animating towards the new state, or skip straight to its end
+    pub(crate) fn animate(&mut self, qh: &QueueHandle<Self>) {
+        if self.config.animation.style == AnimationStyle::None
+            || self.config.animation.duration_ms == 0
+        {
+            return self.handle(Event::Animated, qh);
+        }
+
+        // Frames are already coming for the running one
+        if self.animation.is_none() {
+            self.request_frame(qh);
+        }
+        self.stop_animation();
+
+        let qh = qh.clone();
+        let duration = Duration::from_millis(self.config.animation.duration_ms) + SLACK;
+        let fallback =
+            self.loop_handle
+                .insert_source(Timer::from_duration(duration), move |_, _, app| {
+                    // Its own token goes with the timer
+                    app.animation = None;
+                    app.handle(Event::Animated, &qh);
+                    TimeoutAction::Drop
+                });
+        if let Err(e) = &fallback {
+            eprintln!("Can't schedule finishing the animation: {}", e.error);
+        }
+
+        self.animation = Some(Animation {
+            from: self.openness,
+            started: Instant::now(),
+            fallback: fallback.ok(),
+        });
+        self.draw();
+    }
+
+    // Drop the animation, and the timer finishing it without frames
+    pub(crate) fn stop_animation(&mut self) {
+        if let Some(token) = self
+            .animation
+            .take()
+            .and_then(|animation| animation.fallback)
+        {
+            self.loop_handle.remove(token);
+        }
+    }
+
+    // Advance the animation on a frame callback
+    pub(crate) fn next_frame(&mut self, qh: &QueueHandle<Self>) {
+        let Some(animation) = &self.animation else {
+            return;
+        };
+        let target = match self.state {
+            State::Opening(_) => 1.0,
+            State::Closing(_) => 0.0,
+            _ => {
+                self.stop_animation();
+                return;
+            }
+        };
+
+        // A reversal only has the way back to go
+        let duration = Duration::from_millis(self.config.animation.duration_ms)
+            .mul_f32((target - animation.from).abs());
+        let t = if duration.is_zero() {
+            1.0
+        } else {
+            (animation.started.elapsed().as_secs_f32() / duration.as_secs_f32()).min(1.0)
+        };
+
+        // Quick at first, settling at the end
+        let eased = 1.0 - (1.0 - t).powi(3);
+        self.openness = animation.from + (target - animation.from) * eased;
+
+        if t < 1.0 {
+            self.request_frame(qh);
+            self.draw();
+        } else {
+            self.stop_animation();
+            self.handle(Event::Animated, qh);
+        }
+    }
+
+    // How to scale, shift and fade the frame while opening or closing
+    pub(crate) fn transition(&self) -> Option<(f32, (i32, i32), f32)> {
+        if !matches!(self.state, State::Opening(_) | State::Closing(_)) {
+            return None;
+        }
+
+        let openness = self.openness;
+        let distance = (1.0 - openness) * self.canvas.side as f32 / 4.0;
+
+        match self.config.animation.style {
+            AnimationStyle::None => None,
+            AnimationStyle::Fade => Some((1.0, (0, 0), openness)),
+            AnimationStyle::Scale => Some((0.85 + 0.15 * openness, (0, 0), openness)),
+            AnimationStyle::Slide => {
+                let (dx, dy) = self.config.surface.anchor.direction();
+                let offset = (
+                    (dx as f32 * distance).round() as i32,
+                    (dy as f32 * distance).round() as i32,
+                );
+                Some((1.0, offset, openness))
+            }
+        }
+    }
+
+    fn request_frame(&self, qh: &QueueHandle<Self>) {
+        if let Some(layer) = &self.wl.layer {
+            let surface = layer.wl_surface();
+            surface.frame(qh, surface.clone());
+        }
+    }
+}
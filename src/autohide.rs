@@ -0,0 +1,38 @@
+use smithay_client_toolkit::reexports::calloop::timer::{TimeoutAction, Timer};
+use wayland_client::QueueHandle;
+
+use crate::{Event, Sometime, State};
+
+impl Sometime {
+    // Start counting down to closing the popup again, after any activity
+    pub(crate) fn reset_hide_timer(&mut self, qh: &QueueHandle<Self>) {
+        self.cancel_hide_timer();
+
+        let Some(timeout) = self.hide_after.or(self.config.timeout) else {
+            return;
+        };
+        if !matches!(self.state, State::Awake(_)) {
+            return;
+        }
+
+        let qh = qh.clone();
+        let token =
+            self.loop_handle
+                .insert_source(Timer::from_duration(timeout), move |_, _, app| {
+                    app.hide_token = None;
+                    app.handle(Event::Hide, &qh);
+                    TimeoutAction::Drop
+                });
+
+        match token {
+            Ok(token) => self.hide_token = Some(token),
+            Err(e) => eprintln!("Can't schedule hiding the popup: {}", e.error),
+        }
+    }
+
+    pub(crate) fn cancel_hide_timer(&mut self) {
+        if let Some(token) = self.hide_token.take() {
+            self.loop_handle.remove(token);
+        }
+    }
+}
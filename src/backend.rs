@@ -0,0 +1,501 @@
+// Shapes drawn into a frame, premultiplied BGRA and `side` pixels square,
+// either by hand or with tiny-skia
+
+#[cfg(feature = "tiny-skia")]
+mod skia;
+
+use std::f32::consts::PI;
+
+use crate::{
+    composite,
+    config::{Blending, Renderer},
+    damage::Rect,
+    theme::Bgra,
+};
+
+// Draws shapes over what's in a frame
+pub trait Backend {
+    // A rectangle with corners rounded by `radius`, 0 for sharp ones
+    fn fill_rect(&self, pixels: &mut [u8], rect: Rect, radius: f32, color: Bgra);
+
+    // The outline of `fill_rect`, `width` wide on the inside of its edge
+    fn stroke_rect(&self, pixels: &mut [u8], rect: Rect, radius: f32, width: f32, color: Bgra);
+
+    fn fill_circle(&self, pixels: &mut [u8], center: (f32, f32), radius: f32, color: Bgra);
+
+    // A ring, `width` wide on the inside of the circle
+    fn stroke_circle(
+        &self,
+        pixels: &mut [u8],
+        center: (f32, f32),
+        radius: f32,
+        width: f32,
+        color: Bgra,
+    );
+
+    // A line with round caps
+    fn stroke_line(
+        &self,
+        pixels: &mut [u8],
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: Bgra,
+    );
+
+    // The part of the annulus between `inner` and `outer` that lies within
+    // `end` radians clockwise from 12 o'clock
+    fn fill_sector(
+        &self,
+        pixels: &mut [u8],
+        center: (f32, f32),
+        inner: f32,
+        outer: f32,
+        end: f32,
+        color: Bgra,
+    );
+
+    // The soft shadow of a rounded rectangle, reaching `blur` pixels past
+    // its edges
+    fn shadow(&self, pixels: &mut [u8], rect: Rect, radius: f32, blur: f32, color: Bgra);
+}
+
+// The backend picked in the config, or the software one when it isn't built
+pub fn new(renderer: Renderer, side: i32, blending: Blending) -> Box<dyn Backend> {
+    match renderer {
+        Renderer::Software => Box::new(Software { side, blending }),
+        #[cfg(feature = "tiny-skia")]
+        Renderer::TinySkia => {
+            // tiny-skia always blends on the sRGB values
+            if blending == Blending::Linear {
+                eprintln!("tiny-skia can't blend in linear light, blending in sRGB");
+            }
+            Box::new(skia::Skia::new(side))
+        }
+        #[cfg(not(feature = "tiny-skia"))]
+        Renderer::TinySkia => {
+            eprintln!("Built without the tiny-skia feature, drawing in software");
+            Box::new(Software { side, blending })
+        }
+    }
+}
+
+// Every pixel worked out by hand, blended as the config asks
+pub struct Software {
+    side: i32,
+    blending: Blending,
+}
+
+impl Software {
+    // Blend `color` over the pixels within `bounds`, as much as `coverage`
+    // says for the center of each
+    fn cover(
+        &self,
+        pixels: &mut [u8],
+        bounds: Rect,
+        color: Bgra,
+        coverage: impl Fn(f32, f32) -> f32,
+    ) {
+        let Some(bounds) = bounds.clip(self.side) else {
+            return;
+        };
+
+        for y in bounds.y..bounds.y + bounds.height {
+            for x in bounds.x..bounds.x + bounds.width {
+                let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+                let alpha = (coverage(cx, cy) * 255.0).round() as u8;
+                if alpha > 0 {
+                    let idx = ((y * self.side + x) * 4) as usize;
+                    composite::blend(&mut pixels[idx..idx + 4], color, alpha, self.blending);
+                }
+            }
+        }
+    }
+}
+
+impl Backend for Software {
+    fn fill_rect(&self, pixels: &mut [u8], rect: Rect, radius: f32, color: Bgra) {
+        self.cover(pixels, rect, color, |x, y| {
+            coverage(rect_distance(rect, radius, x, y))
+        });
+    }
+
+    fn stroke_rect(&self, pixels: &mut [u8], rect: Rect, radius: f32, width: f32, color: Bgra) {
+        self.cover(pixels, rect, color, |x, y| {
+            let distance = rect_distance(rect, radius, x, y);
+            coverage(distance) - coverage(distance + width)
+        });
+    }
+
+    fn fill_circle(&self, pixels: &mut [u8], center: (f32, f32), radius: f32, color: Bgra) {
+        self.cover(pixels, circle_bounds(center, radius), color, |x, y| {
+            coverage((x - center.0).hypot(y - center.1) - radius)
+        });
+    }
+
+    fn stroke_circle(
+        &self,
+        pixels: &mut [u8],
+        center: (f32, f32),
+        radius: f32,
+        width: f32,
+        color: Bgra,
+    ) {
+        self.cover(pixels, circle_bounds(center, radius), color, |x, y| {
+            let distance = (x - center.0).hypot(y - center.1) - radius;
+            coverage(distance) - coverage(distance + width)
+        });
+    }
+
+    // A soft round brush stamped along the line, a pixel at a time
+    fn stroke_line(
+        &self,
+        pixels: &mut [u8],
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: Bgra,
+    ) {
+        let dx = to.0 - from.0;
+        let dy = to.1 - from.1;
+        let steps = dx.abs().max(dy.abs()) as i32;
+
+        if steps == 0 {
+            return;
+        }
+
+        let x_inc = dx / steps as f32;
+        let y_inc = dy / steps as f32;
+
+        let half_width = width / 2.0;
+        let search_radius = (half_width + 2.0).ceil() as i32;
+        let inner_radius = half_width - 1.0;
+        let outer_radius = half_width + 1.0;
+        let inner_radius_sq = inner_radius * inner_radius;
+        let outer_radius_sq = outer_radius * outer_radius;
+
+        let (mut x, mut y) = from;
+
+        for _ in 0..=steps {
+            for dy_offset in -search_radius..=search_radius {
+                for dx_offset in -search_radius..=search_radius {
+                    let px = (x + dx_offset as f32).round() as i32;
+                    let py = (y + dy_offset as f32).round() as i32;
+
+                    if px >= 0 && px < self.side && py >= 0 && py < self.side {
+                        let squared_dist = (px as f32 - x).powi(2) + (py as f32 - y).powi(2);
+
+                        // Fade out at the edges
+                        let alpha = if squared_dist <= inner_radius_sq {
+                            1.0
+                        } else if squared_dist <= outer_radius_sq {
+                            1.0 - (squared_dist - inner_radius_sq)
+                                / (outer_radius_sq - inner_radius_sq)
+                        } else {
+                            continue;
+                        };
+
+                        let idx = ((py * self.side + px) * 4) as usize;
+                        composite::blend(
+                            &mut pixels[idx..idx + 4],
+                            color,
+                            (alpha * 255.0) as u8,
+                            self.blending,
+                        );
+                    }
+                }
+            }
+            x += x_inc;
+            y += y_inc;
+        }
+    }
+
+    fn fill_sector(
+        &self,
+        pixels: &mut [u8],
+        center: (f32, f32),
+        inner: f32,
+        outer: f32,
+        end: f32,
+        color: Bgra,
+    ) {
+        let Some(bounds) = circle_bounds(center, outer).clip(self.side) else {
+            return;
+        };
+        let angle = |x: i32, dy: f32| (x as f32 - center.0).atan2(-dy).rem_euclid(2.0 * PI);
+        // How far along a row the edge at `end` moves per row from the center
+        let slope = end.tan();
+
+        for y in bounds.y..bounds.y + bounds.height {
+            let dy = y as f32 - center.1;
+            let reach_sq = outer * outer - dy * dy;
+            if reach_sq < 0.0 {
+                continue;
+            }
+            let reach = reach_sq.sqrt();
+            let hole = (inner * inner - dy * dy).max(0.0).sqrt();
+
+            // Either side of the center the angle only grows or only shrinks
+            // along the row, so the ends of each half tell what's in it
+            let left = (
+                (center.0 - reach).ceil() as i32,
+                ((center.0 - hole).floor() as i32).min(center.0.ceil() as i32 - 1),
+            );
+            let right = (
+                ((center.0 + hole).ceil() as i32).max(center.0.ceil() as i32),
+                (center.0 + reach).floor() as i32,
+            );
+
+            for (first, last) in [left, right] {
+                let first = first.max(bounds.x);
+                let last = last.min(bounds.x + bounds.width - 1);
+                if first > last {
+                    continue;
+                }
+
+                // Where the edge at `end` crosses the row, settled on the
+                // pixel the angle says as rounding can land one off
+                let split = center.0 - dy * slope;
+                let inside = |x: i32| angle(x, dy) < end;
+                let (first, last) = match (inside(first), inside(last)) {
+                    (true, true) => (first, last),
+                    (false, false) => continue,
+                    (true, false) => {
+                        let mut edge = (split.ceil() as i32).clamp(first, last);
+                        while edge > first && !inside(edge - 1) {
+                            edge -= 1;
+                        }
+                        while inside(edge) {
+                            edge += 1;
+                        }
+                        (first, edge - 1)
+                    }
+                    (false, true) => {
+                        let mut edge = (split.floor() as i32).clamp(first, last);
+                        while edge < last && !inside(edge + 1) {
+                            edge += 1;
+                        }
+                        while inside(edge) {
+                            edge -= 1;
+                        }
+                        (edge + 1, last)
+                    }
+                };
+                if first > last {
+                    continue;
+                }
+
+                let row = (y * self.side * 4) as usize;
+                let span = &mut pixels[row + first as usize * 4..row + (last + 1) as usize * 4];
+                composite::blend_span(span, color, 255, self.blending);
+            }
+        }
+    }
+
+    fn shadow(&self, pixels: &mut [u8], rect: Rect, radius: f32, blur: f32, color: Bgra) {
+        let side = self.side as usize;
+        let mut mask = vec![0u8; side * side];
+
+        if let Some(bounds) = rect.clip(self.side) {
+            for y in bounds.y..bounds.y + bounds.height {
+                for x in bounds.x..bounds.x + bounds.width {
+                    let distance = rect_distance(rect, radius, x as f32 + 0.5, y as f32 + 0.5);
+                    mask[y as usize * side + x as usize] = (coverage(distance) * 255.0) as u8;
+                }
+            }
+        }
+        box_blur(&mut mask, side, blur);
+
+        for (pixel, alpha) in pixels.chunks_exact_mut(4).zip(mask) {
+            if alpha > 0 {
+                composite::blend(pixel, color, alpha, self.blending);
+            }
+        }
+    }
+}
+
+// How much of a pixel a shape covers, from the distance of its center to
+// the shape's edge, negative inside
+fn coverage(distance: f32) -> f32 {
+    (0.5 - distance).clamp(0.0, 1.0)
+}
+
+// Signed distance to the edge of a rounded rectangle
+fn rect_distance(rect: Rect, radius: f32, x: f32, y: f32) -> f32 {
+    let half_width = rect.width as f32 / 2.0;
+    let half_height = rect.height as f32 / 2.0;
+    let radius = radius.clamp(0.0, half_width.min(half_height));
+
+    let qx = (x - rect.x as f32 - half_width).abs() - (half_width - radius);
+    let qy = (y - rect.y as f32 - half_height).abs() - (half_height - radius);
+    qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius
+}
+
+fn circle_bounds(center: (f32, f32), radius: f32) -> Rect {
+    Rect::around(
+        (center.0 - radius).floor() as i32,
+        (center.1 - radius).floor() as i32,
+        (center.0 + radius).ceil() as i32,
+        (center.1 + radius).ceil() as i32,
+    )
+}
+
+// Spread a coverage mask about `blur` pixels out, with three box blurs
+// standing in for a gaussian
+pub(crate) fn box_blur(mask: &mut [u8], side: usize, blur: f32) {
+    let radius = (blur / 3.0).round() as usize;
+    if radius == 0 {
+        return;
+    }
+    let mut line = vec![0u8; side];
+
+    for _ in 0..3 {
+        for y in 0..side {
+            blur_line(mask, y * side, 1, side, radius, &mut line);
+        }
+        for x in 0..side {
+            blur_line(mask, x, side, side, radius, &mut line);
+        }
+    }
+}
+
+// Average `len` pixels `stride` apart over a window `radius` wide each way,
+// with nothing past the ends
+fn blur_line(
+    mask: &mut [u8],
+    start: usize,
+    stride: usize,
+    len: usize,
+    radius: usize,
+    line: &mut [u8],
+) {
+    for (i, value) in line.iter_mut().enumerate().take(len) {
+        *value = mask[start + i * stride];
+    }
+
+    let width = 2 * radius as u32 + 1;
+    let mut sum: u32 = line[..(radius + 1).min(len)]
+        .iter()
+        .map(|&v| v as u32)
+        .sum();
+
+    for i in 0..len {
+        mask[start + i * stride] = ((sum + width / 2) / width) as u8;
+        if i + radius + 1 < len {
+            sum += line[i + radius + 1] as u32;
+        }
+        if i >= radius {
+            sum -= line[i - radius] as u32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backend, Rect, Software, box_blur};
+    use crate::{config::Blending, theme::Bgra};
+    use std::f32::consts::PI;
+
+    const SIDE: i32 = 32;
+
+    fn alpha(pixels: &[u8], x: i32, y: i32) -> u8 {
+        pixels[((y * SIDE + x) * 4 + 3) as usize]
+    }
+
+    #[test]
+    fn sharp_and_rounded_rects() {
+        let backend = Software {
+            side: SIDE,
+            blending: Blending::Srgb,
+        };
+        let rect = Rect {
+            x: 4,
+            y: 4,
+            width: 16,
+            height: 8,
+        };
+        let color = Bgra::from_rgba(255, 255, 255, 255);
+
+        // On the pixel grid, nothing's left half covered
+        let mut pixels = vec![0; (SIDE * SIDE * 4) as usize];
+        backend.fill_rect(&mut pixels, rect, 0.0, color);
+        for y in 0..SIDE {
+            for x in 0..SIDE {
+                let inside = (4..20).contains(&x) && (4..12).contains(&y);
+                assert_eq!(alpha(&pixels, x, y), if inside { 255 } else { 0 });
+            }
+        }
+
+        // Rounded off at the corners alone
+        let mut pixels = vec![0; (SIDE * SIDE * 4) as usize];
+        backend.fill_rect(&mut pixels, rect, 3.0, color);
+        assert_eq!(alpha(&pixels, 4, 4), 0);
+        assert_eq!(alpha(&pixels, 19, 11), 0);
+        assert_eq!(alpha(&pixels, 12, 4), 255);
+        assert_eq!(alpha(&pixels, 4, 8), 255);
+
+        // Only the edge of it
+        let mut pixels = vec![0; (SIDE * SIDE * 4) as usize];
+        backend.stroke_rect(&mut pixels, rect, 0.0, 2.0, color);
+        assert_eq!(alpha(&pixels, 5, 5), 255);
+        assert_eq!(alpha(&pixels, 6, 6), 0);
+        assert_eq!(alpha(&pixels, 18, 10), 255);
+    }
+
+    #[test]
+    fn sector_spans_follow_the_angle() {
+        let backend = Software {
+            side: SIDE,
+            blending: Blending::Srgb,
+        };
+        let color = Bgra::from_rgba(255, 255, 255, 255);
+        let ends = [
+            0.3,
+            1.0,
+            PI / 2.0,
+            2.5,
+            PI,
+            4.0,
+            3.0 * PI / 2.0,
+            5.9,
+            2.0 * PI,
+        ];
+
+        for (center, end) in [(16.0, 16.0), (15.5, 16.0), (16.3, 15.7)]
+            .into_iter()
+            .flat_map(|center| ends.map(|end| (center, end)))
+        {
+            let mut pixels = vec![0; (SIDE * SIDE * 4) as usize];
+            backend.fill_sector(&mut pixels, center, 4.0, 12.0, end, color);
+
+            // Each pixel as far from the center and as far around as it is
+            let mut wrong = 0;
+            for y in 0..SIDE {
+                for x in 0..SIDE {
+                    let (dx, dy) = (x as f32 - center.0, y as f32 - center.1);
+                    let dist = (dx * dx + dy * dy).sqrt();
+                    let inside =
+                        (4.0..=12.0).contains(&dist) && dx.atan2(-dy).rem_euclid(2.0 * PI) < end;
+                    if inside != (alpha(&pixels, x, y) == 255) {
+                        wrong += 1;
+                    }
+                }
+            }
+            assert_eq!(wrong, 0, "at {end} around {center:?}");
+        }
+    }
+
+    #[test]
+    fn blur_spreads_evenly() {
+        let side = 16;
+        let mut mask = vec![0; side * side];
+        mask[8 * side + 8] = 255;
+        box_blur(&mut mask, side, 3.0);
+
+        // Symmetric and fading out from the middle
+        assert!(mask[8 * side + 8] > mask[8 * side + 9]);
+        assert_eq!(mask[8 * side + 7], mask[8 * side + 9]);
+        assert_eq!(mask[7 * side + 8], mask[9 * side + 8]);
+        assert_eq!(mask[8 * side + 12], 0);
+    }
+}
@@ -0,0 +1,267 @@
+use std::f32::consts::PI;
+use tiny_skia::{FillRule, LineCap, Mask, Path, PathBuilder, PixmapMut, Shader, Stroke, Transform};
+
+use super::{Backend, box_blur};
+use crate::{damage::Rect, theme::Bgra};
+
+// Paths filled and stroked by tiny-skia, antialiased and blended in sRGB
+pub struct Skia {
+    side: i32,
+}
+
+impl Skia {
+    pub fn new(side: i32) -> Self {
+        Self { side }
+    }
+
+    fn pixmap<'a>(&self, pixels: &'a mut [u8]) -> Option<PixmapMut<'a>> {
+        PixmapMut::from_bytes(pixels, self.side as u32, self.side as u32)
+    }
+
+    fn fill(&self, pixels: &mut [u8], path: Option<Path>, color: Bgra, fill_rule: FillRule) {
+        if let (Some(mut pixmap), Some(path)) = (self.pixmap(pixels), path) {
+            pixmap.fill_path(
+                &path,
+                &skia_paint(color),
+                fill_rule,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    fn stroke(&self, pixels: &mut [u8], path: Option<Path>, color: Bgra, stroke: Stroke) {
+        if let (Some(mut pixmap), Some(path)) = (self.pixmap(pixels), path) {
+            pixmap.stroke_path(
+                &path,
+                &skia_paint(color),
+                &stroke,
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+}
+
+impl Backend for Skia {
+    fn fill_rect(&self, pixels: &mut [u8], rect: Rect, radius: f32, color: Bgra) {
+        let path = rounded_rect(
+            rect.x as f32,
+            rect.y as f32,
+            rect.width as f32,
+            rect.height as f32,
+            radius,
+        );
+        self.fill(pixels, path, color, FillRule::Winding);
+    }
+
+    fn stroke_rect(&self, pixels: &mut [u8], rect: Rect, radius: f32, width: f32, color: Bgra) {
+        // Stroked down the middle of the edge, so half as far in
+        let inset = width / 2.0;
+        let path = rounded_rect(
+            rect.x as f32 + inset,
+            rect.y as f32 + inset,
+            rect.width as f32 - width,
+            rect.height as f32 - width,
+            radius - inset,
+        );
+        let stroke = Stroke {
+            width,
+            ..Stroke::default()
+        };
+        self.stroke(pixels, path, color, stroke);
+    }
+
+    fn fill_circle(&self, pixels: &mut [u8], center: (f32, f32), radius: f32, color: Bgra) {
+        let path = PathBuilder::from_circle(center.0, center.1, radius);
+        self.fill(pixels, path, color, FillRule::Winding);
+    }
+
+    fn stroke_circle(
+        &self,
+        pixels: &mut [u8],
+        center: (f32, f32),
+        radius: f32,
+        width: f32,
+        color: Bgra,
+    ) {
+        let path = PathBuilder::from_circle(center.0, center.1, radius - width / 2.0);
+        let stroke = Stroke {
+            width,
+            ..Stroke::default()
+        };
+        self.stroke(pixels, path, color, stroke);
+    }
+
+    fn stroke_line(
+        &self,
+        pixels: &mut [u8],
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: Bgra,
+    ) {
+        let mut builder = PathBuilder::new();
+        builder.move_to(from.0, from.1);
+        builder.line_to(to.0, to.1);
+        let stroke = Stroke {
+            width,
+            line_cap: LineCap::Round,
+            ..Stroke::default()
+        };
+        self.stroke(pixels, builder.finish(), color, stroke);
+    }
+
+    fn fill_sector(
+        &self,
+        pixels: &mut [u8],
+        center: (f32, f32),
+        inner: f32,
+        outer: f32,
+        end: f32,
+        color: Bgra,
+    ) {
+        let mut builder = PathBuilder::new();
+
+        if end >= 2.0 * PI {
+            builder.push_circle(center.0, center.1, outer);
+            if inner > 0.0 {
+                builder.push_circle(center.0, center.1, inner);
+            }
+        } else if end > 0.0 {
+            // Clockwise from 12 o'clock, a couple of pixels per segment
+            let point = |radius: f32, angle: f32| {
+                (
+                    center.0 + radius * angle.sin(),
+                    center.1 - radius * angle.cos(),
+                )
+            };
+            let segments = (end * outer / 2.0).ceil().max(1.0) as u32;
+            let angle = |i: u32| end * i as f32 / segments as f32;
+
+            let (x, y) = point(outer, 0.0);
+            builder.move_to(x, y);
+            for i in 1..=segments {
+                let (x, y) = point(outer, angle(i));
+                builder.line_to(x, y);
+            }
+            if inner > 0.0 {
+                for i in (0..=segments).rev() {
+                    let (x, y) = point(inner, angle(i));
+                    builder.line_to(x, y);
+                }
+            } else {
+                builder.line_to(center.0, center.1);
+            }
+            builder.close();
+        }
+
+        self.fill(pixels, builder.finish(), color, FillRule::EvenOdd);
+    }
+
+    fn shadow(&self, pixels: &mut [u8], rect: Rect, radius: f32, blur: f32, color: Bgra) {
+        let (Some(mut mask), Some(path)) = (
+            Mask::new(self.side as u32, self.side as u32),
+            rounded_rect(
+                rect.x as f32,
+                rect.y as f32,
+                rect.width as f32,
+                rect.height as f32,
+                radius,
+            ),
+        ) else {
+            return;
+        };
+        mask.fill_path(&path, FillRule::Winding, true, Transform::identity());
+        box_blur(mask.data_mut(), self.side as usize, blur);
+
+        let full = tiny_skia::Rect::from_xywh(0.0, 0.0, self.side as f32, self.side as f32);
+        if let (Some(mut pixmap), Some(full)) = (self.pixmap(pixels), full) {
+            let paint = skia_paint(color);
+            pixmap.fill_rect(full, &paint, Transform::identity(), Some(&mask));
+        }
+    }
+}
+
+// The frame is BGRA where tiny-skia expects RGBA, so red and blue trade
+// places on the way in
+fn skia_color(color: Bgra) -> tiny_skia::Color {
+    tiny_skia::Color::from_rgba8(color.b(), color.g(), color.r(), color.a())
+}
+
+fn skia_paint(color: Bgra) -> tiny_skia::Paint<'static> {
+    let shader = Shader::SolidColor(skia_color(color));
+
+    tiny_skia::Paint {
+        shader,
+        anti_alias: true,
+        ..tiny_skia::Paint::default()
+    }
+}
+
+// Quarter circles as cubics at the corners
+fn rounded_rect(x: f32, y: f32, width: f32, height: f32, radius: f32) -> Option<Path> {
+    let radius = radius.clamp(0.0, width.min(height) / 2.0);
+    if radius == 0.0 {
+        return tiny_skia::Rect::from_xywh(x, y, width, height).map(PathBuilder::from_rect);
+    }
+
+    // How far the control points sit from the corner
+    let k = radius * (1.0 - 0.552_284_8);
+    let (right, bottom) = (x + width, y + height);
+
+    let mut builder = PathBuilder::new();
+    builder.move_to(x + radius, y);
+    builder.line_to(right - radius, y);
+    builder.cubic_to(right - k, y, right, y + k, right, y + radius);
+    builder.line_to(right, bottom - radius);
+    builder.cubic_to(right, bottom - k, right - k, bottom, right - radius, bottom);
+    builder.line_to(x + radius, bottom);
+    builder.cubic_to(x + k, bottom, x, bottom - k, x, bottom - radius);
+    builder.line_to(x, y + radius);
+    builder.cubic_to(x, y + k, x + k, y, x + radius, y);
+    builder.close();
+    builder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Skia;
+    use crate::{
+        backend::{Backend, Software},
+        config::Blending,
+        damage::Rect,
+        theme::Bgra,
+    };
+
+    const SIDE: i32 = 64;
+
+    #[test]
+    fn matches_software() {
+        let software = Software {
+            side: SIDE,
+            blending: Blending::Srgb,
+        };
+        let skia = Skia::new(SIDE);
+        let color = Bgra::from_rgba(245, 194, 231, 242);
+        let rect = Rect {
+            x: 8,
+            y: 12,
+            width: 40,
+            height: 30,
+        };
+
+        let draw = |backend: &dyn Backend| {
+            let mut pixels = vec![0; (SIDE * SIDE * 4) as usize];
+            backend.fill_circle(&mut pixels, (32.0, 32.0), 20.5, color);
+            backend.stroke_rect(&mut pixels, rect, 6.0, 2.0, color);
+            backend.fill_sector(&mut pixels, (32.0, 32.0), 4.0, 12.0, 2.0, color);
+            pixels
+        };
+
+        // Off by a little along the antialiased edges, at most
+        let (a, b) = (draw(&software), draw(&skia));
+        let off: u32 = a.iter().zip(&b).map(|(a, b)| a.abs_diff(*b) as u32).sum();
+        assert!(off / (a.len() as u32) < 2, "{off}");
+    }
+}
@@ -8,44 +8,128 @@ use smithay_client_toolkit::reexports::{
 use std::time::Duration;
 use wayland_client::{Connection, globals};
 
-use sometime::{Sometime, State, View, Wayland, flock, ipc};
+use sometime::{
+    Command, Config, Invocation, Sometime, State, View, Wayland, flock,
+    ipc::{self, Reply},
+    wallclock,
+};
 
 fn main() -> anyhow::Result<()> {
     let _lock = flock::try_acquire_daemon_lock()?;
 
+    // Kept for old setups, a peek for every popup
     let exit_on_release = std::env::args()
         .nth(1)
         .is_some_and(|arg| arg == "--exit-on-release");
+    if exit_on_release {
+        eprintln!("--exit-on-release is deprecated, open the popup with `sometime <view> --peek`");
+    }
+
+    let config = Config::load()?;
 
     let conn = Connection::connect_to_env()?;
     let (globals, event_queue) = globals::registry_queue_init(&conn)?;
     let qh = event_queue.handle();
 
-    let wl = Wayland::new(&globals, &qh)?;
-    let mut app = Sometime::new(wl, exit_on_release);
-
     let mut event_loop = EventLoop::try_new()?;
     let loop_handle = event_loop.handle();
 
+    let wl = Wayland::new(&globals, &qh)?;
+    let mut app = Sometime::new(wl, config, loop_handle.clone());
+    app.always_peek = exit_on_release;
+    app.load_alarms(&qh)?;
+    app.load_pomodoro()?;
+
     WaylandSource::new(conn, event_queue).insert(loop_handle.clone())?;
 
     let ipc_listener = ipc::setup_listener()?;
     let event_source = Generic::new(ipc_listener, Interest::READ, Mode::Level);
-    loop_handle.insert_source(event_source, move |readiness, listener, app| {
-        if readiness.readable
-            && let Ok((stream, _)) = listener.accept()
-            && let Ok(cmd) = ipc::recv_cmd(stream)
-        {
-            app.handle(cmd.into(), &qh);
+    let ipc_qh = qh.clone();
+    let ipc_handle = loop_handle.clone();
+    loop_handle.insert_source(event_source, move |readiness, listener, _| {
+        if !readiness.readable {
+            return Ok(PostAction::Continue);
+        }
+        let Ok((stream, _)) = listener.accept() else {
+            return Ok(PostAction::Continue);
+        };
+        let Ok(reader) = stream.try_clone() else {
+            return Ok(PostAction::Continue);
+        };
+        if stream.set_nonblocking(true).is_err() {
+            return Ok(PostAction::Continue);
+        }
+
+        // Read the request as it arrives, so a slow client doesn't stall the daemon
+        let qh = ipc_qh.clone();
+        let mut line = Vec::new();
+        let mut stream = Some(stream);
+        let request = Generic::new(reader, Interest::READ, Mode::Level);
+        let inserted = ipc_handle.insert_source(request, move |_, reader, app| {
+            match ipc::read_request(reader, &mut line) {
+                Ok(false) => return Ok(PostAction::Continue),
+                Ok(true) => {}
+                Err(_) => return Ok(PostAction::Remove),
+            }
+            let Some(stream) = stream.take() else {
+                return Ok(PostAction::Remove);
+            };
+            let Ok((cmd, client)) = ipc::recv_cmd(stream, &line) else {
+                return Ok(PostAction::Remove);
+            };
+
+            let reply = match cmd {
+                Ok(Invocation {
+                    command: Command::Subscribe,
+                    ..
+                }) => {
+                    if let Err(e) = app.subscribe(client) {
+                        eprintln!("Can't add a subscriber: {}", e);
+                    }
+                    return Ok(PostAction::Remove);
+                }
+                Ok(invocation) => app.command(invocation, &qh).unwrap_or_else(Reply::error),
+                Err(e) => Reply::error(e),
+            };
+            client.reply(reply).ok();
+            Ok(PostAction::Remove)
+        });
+        if let Err(e) = inserted {
+            eprintln!("Can't read a request: {}", e.error);
+        }
+        Ok(PostAction::Continue)
+    })?;
+
+    // Clock set, NTP step or resume from suspend
+    let clock_watch = Generic::new(wallclock::ClockWatch::new()?, Interest::READ, Mode::Level);
+    let clock_qh = qh.clone();
+    loop_handle.insert_source(clock_watch, move |_, watch, app| {
+        if watch.jumped() {
+            app.clock_changed(&clock_qh);
         }
         Ok(PostAction::Continue)
     })?;
 
+    // A new local time zone
+    match wallclock::TzWatch::new() {
+        Ok(tz_watch) => {
+            let tz_watch = Generic::new(tz_watch, Interest::READ, Mode::Level);
+            let tz_qh = qh.clone();
+            loop_handle.insert_source(tz_watch, move |_, watch, app| {
+                if watch.changed() {
+                    app.tz_changed(&tz_qh);
+                }
+                Ok(PostAction::Continue)
+            })?;
+        }
+        Err(e) => eprintln!("Not watching /etc/localtime: {}", e),
+    }
+
     loop {
         event_loop.dispatch(None, &mut app)?;
 
         if matches!(app.state, State::Awake(_)) && !app.is_happening {
-            let timer = Timer::from_duration(next_tick());
+            let timer = Timer::from_duration(next_tick(app.state));
 
             loop_handle
                 .insert_source(timer, |_, _, app| {
@@ -54,16 +138,17 @@ fn main() -> anyhow::Result<()> {
                         return TimeoutAction::Drop;
                     };
 
-                    let now = Local::now();
+                    let now = app.local_now();
 
                     if match view {
-                        View::Clock => app.last_second != now.second(),
+                        View::Clock | View::World | View::Timer => app.last_second != now.second(),
                         View::Calendar => app.last_day != now.day(),
+                        View::Stopwatch => app.stopwatch.is_running(),
                     } {
                         app.draw();
                     }
 
-                    TimeoutAction::ToDuration(next_tick())
+                    TimeoutAction::ToDuration(next_tick(app.state))
                 })
                 .map_err(|e| e.error)?;
 
@@ -80,7 +165,13 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn next_tick() -> Duration {
+fn next_tick(state: State) -> Duration {
+    // The stopwatch shows tenths of a second
+    let period = match state {
+        State::Awake(View::Stopwatch) => 100,
+        _ => 1000,
+    };
+
     let ms_since_last_sec = Local::now().timestamp_subsec_millis();
-    Duration::from_millis((1000 - ms_since_last_sec) as u64)
+    Duration::from_millis((period - ms_since_last_sec % period) as u64)
 }
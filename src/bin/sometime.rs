@@ -1,16 +1,83 @@
-use sometime::ipc;
+use sometime::{
+    Command, Invocation,
+    ipc::{self, Reply, Response},
+};
+
+const USAGE: &str = "\
+Usage: sometime [--json] <command>
+
+  clock [--tz ZONE]
+  calendar
+  world
+  timer [DURATION [--name NAME] | list | cancel NAME]
+  stopwatch [start | stop | toggle | lap | reset]
+  alarm <add HH:MM [--days DAYS] [--label LABEL] | remove N | list>
+  pomodoro <start | pause | skip | status>
+  show <VIEW> | switch <VIEW> | hide
+  status
+  subscribe
+  dismiss
+
+  VIEW is clock, calendar, world, timer or stopwatch
+
+  --timeout DURATION  close the popup this opens after a while
+  --peek              close it once the keys held to open it are released
+  --json              print the daemon's reply as JSON";
 
 fn main() {
-    if let Some(cmd) = std::env::args().nth(1).and_then(|arg| arg.parse().ok()) {
-        match ipc::invoke_daemon(cmd) {
-            Ok(response) => println!("{}", response),
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Only before the command, where it can't be part of a name
+    let json = args.first().is_some_and(|arg| arg == "--json");
+    if json {
+        args.remove(0);
+    }
+
+    match Invocation::from_args(args.iter().map(String::as_str)) {
+        Ok(Invocation {
+            command: Command::Subscribe,
+            ..
+        }) => {
+            let result = ipc::subscribe(|line, notice| {
+                if json {
+                    println!("{}", line);
+                } else {
+                    println!("{}", notice);
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Ok(cmd) => match ipc::invoke_daemon(&cmd) {
+            Ok(reply) if json => {
+                let failed = matches!(reply, Reply::Error { .. });
+                let response = Response {
+                    version: ipc::VERSION,
+                    reply,
+                };
+                println!("{}", serde_json::to_string(&response).unwrap_or_default());
+                if failed {
+                    std::process::exit(1);
+                }
+            }
+            Ok(reply @ Reply::Error { .. }) => {
+                eprintln!("{}", reply);
+                std::process::exit(1);
+            }
+            Ok(reply) => println!("{}", reply),
             Err(e) => {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
+        },
+        Err(e) => {
+            if !args.is_empty() {
+                eprintln!("Error: {}", e);
+            }
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
         }
-    } else {
-        eprintln!("Usage: sometime <clock|calendar|dismiss>");
-        std::process::exit(1);
     }
 }
@@ -0,0 +1,70 @@
+use smithay_client_toolkit::{
+    compositor::{CompositorState, Region},
+    globals::GlobalData,
+};
+use wayland_client::{QueueHandle, globals::GlobalList, protocol::wl_surface::WlSurface};
+use wayland_protocols::ext::background_effect::v1::client::{
+    ext_background_effect_manager_v1::ExtBackgroundEffectManagerV1,
+    ext_background_effect_surface_v1::ExtBackgroundEffectSurfaceV1,
+};
+
+use crate::{Sometime, damage::Rect};
+
+// The compositor blurring what's behind the popup, when it has
+// `ext-background-effect` and says it can
+pub struct Blur {
+    manager: Option<ExtBackgroundEffectManagerV1>,
+    // Told by the `capabilities` event, and again whenever that changes
+    pub capable: bool,
+    effect: Option<ExtBackgroundEffectSurfaceV1>,
+    // What was last asked to be blurred, empty for nothing
+    region: Vec<Rect>,
+}
+
+impl Blur {
+    pub fn bind(globals: &GlobalList, qh: &QueueHandle<Sometime>) -> Self {
+        Self {
+            manager: globals.bind(qh, 1..=1, GlobalData).ok(),
+            capable: false,
+            effect: None,
+            region: Vec::new(),
+        }
+    }
+
+    // Set up a new layer surface for blurring
+    pub fn attach(&mut self, surface: &WlSurface, qh: &QueueHandle<Sometime>) {
+        if let Some(manager) = &self.manager {
+            self.effect = Some(manager.get_background_effect(surface, qh, ()));
+        }
+    }
+
+    // Blur behind `rects` from the next commit, or nothing when it's empty;
+    // without the protocol the popup is just see-through
+    pub fn set(&mut self, compositor: &CompositorState, rects: Vec<Rect>) {
+        let Some(effect) = &self.effect else {
+            return;
+        };
+        let rects = if self.capable { rects } else { Vec::new() };
+        if rects == self.region {
+            return;
+        }
+
+        if rects.is_empty() {
+            effect.set_blur_region(None);
+        } else if let Ok(region) = Region::new(compositor) {
+            for rect in &rects {
+                region.add(rect.x, rect.y, rect.width, rect.height);
+            }
+            effect.set_blur_region(Some(region.wl_region()));
+        }
+        self.region = rects;
+    }
+
+    // Before the surface goes away
+    pub fn detach(&mut self) {
+        if let Some(effect) = self.effect.take() {
+            effect.destroy();
+        }
+        self.region.clear();
+    }
+}
@@ -0,0 +1,100 @@
+use smithay_client_toolkit::shm::{
+    CreatePoolError, Shm,
+    slot::{Buffer, SlotPool},
+};
+use wayland_client::protocol::{wl_shm::Format, wl_surface::WlSurface};
+
+use crate::{
+    canvas::Contents,
+    damage::{self, Rect},
+};
+
+// Enough for the compositor to hold one or two while the next is drawn
+const RING: usize = 3;
+
+// A few `wl_buffer`s taking turns, each drawn into again only once the
+// compositor released it
+pub struct Buffers {
+    pool: SlotPool,
+    side: i32,
+    // The least recently drawn first, the one being drawn last
+    ring: Vec<Slot>,
+    // Changed since the last commit, damaged on the next one
+    unsent: Vec<Rect>,
+}
+
+struct Slot {
+    buffer: Buffer,
+    contents: Contents,
+}
+
+impl Buffers {
+    pub fn new(side: i32, shm: &Shm) -> Result<Self, CreatePoolError> {
+        Ok(Self {
+            pool: SlotPool::new((side * side * 4) as usize * RING, shm)?,
+            side,
+            ring: Vec::new(),
+            unsent: Vec::new(),
+        })
+    }
+
+    // A buffer to draw the next frame into, with what it holds already
+    pub fn next(&mut self) -> Option<(&mut [u8], &mut Contents)> {
+        let released = self
+            .ring
+            .iter()
+            .position(|slot| slot.buffer.canvas(&mut self.pool).is_some());
+
+        match released {
+            Some(i) => {
+                let slot = self.ring.remove(i);
+                self.ring.push(slot);
+            }
+            None => {
+                // All held by the compositor, let go of the oldest once it's released
+                if self.ring.len() == RING {
+                    self.ring.remove(0);
+                }
+
+                let side = self.side;
+                let (buffer, _) = self
+                    .pool
+                    .create_buffer(side, side, side * 4, Format::Argb8888)
+                    .ok()?;
+                self.ring.push(Slot {
+                    buffer,
+                    contents: Contents::default(),
+                });
+            }
+        }
+
+        let slot = self.ring.last_mut()?;
+        let pixels = slot.buffer.canvas(&mut self.pool)?;
+        Some((pixels, &mut slot.contents))
+    }
+
+    // Attach the buffer from `next`, damaging only what changed; committing
+    // is up to the caller
+    pub fn attach(&mut self, surface: &WlSurface, damage: &[Rect]) {
+        for rect in damage {
+            damage::add(&mut self.unsent, *rect);
+        }
+
+        let Some(slot) = self.ring.last() else {
+            return;
+        };
+        if slot.buffer.attach_to(surface).is_err() {
+            return;
+        }
+
+        for rect in self.unsent.drain(..) {
+            surface.damage_buffer(rect.x, rect.y, rect.width, rect.height);
+        }
+    }
+
+    // Forget the buffers of a destroyed surface, a new one starts from scratch
+    pub fn reset(&mut self) {
+        self.ring.clear();
+        self.unsent = vec![Rect::full(self.side)];
+    }
+}
@@ -0,0 +1,48 @@
+use chrono::{Datelike, Months, NaiveDate};
+
+use crate::{Sometime, State, View, ipc::Notice};
+
+impl Sometime {
+    // The first of the month on the calendar
+    pub(crate) fn calendar_month(&self) -> NaiveDate {
+        let first = self.local_now().date().with_day(1).unwrap();
+        let months = Months::new(self.month_offset.unsigned_abs());
+
+        if self.month_offset < 0 {
+            first - months
+        } else {
+            first + months
+        }
+    }
+
+    // Move the calendar forward, or back when negative
+    pub(crate) fn turn_month(&mut self, months: i32) {
+        if self.state != State::Awake(View::Calendar) {
+            return;
+        }
+
+        self.month_offset += months;
+        self.draw();
+    }
+
+    // Select the day under a point on the surface, if there's one
+    pub(crate) fn select_day_at(&mut self, x: f64, y: f64) -> bool {
+        if self.state != State::Awake(View::Calendar) {
+            return false;
+        }
+
+        let month = self.calendar_month();
+        let Some(date) = self
+            .canvas
+            .calendar_day_at(month, x, y)
+            .and_then(|day| month.with_day(day))
+        else {
+            return false;
+        };
+
+        self.selected = Some(date);
+        self.notify(Notice::DaySelected { date });
+        self.draw();
+        true
+    }
+}
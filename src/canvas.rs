@@ -1,206 +1,737 @@
-use chrono::{Datelike, NaiveDate};
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike};
 use cosmic_text::{
-    Align, Attrs, Buffer, Color, FontSystem, Metrics, Shaping, SwashCache,
+    Align, Attrs, Buffer, Color, Fallback, Family, FontSystem, Metrics, PlatformFallback, Shaping,
+    Style, SwashCache, Weight,
     fontdb::{Database, Source},
 };
-use std::{f32::consts::PI, sync::Arc};
+use std::{collections::HashMap, f32::consts::PI, sync::Arc};
+use unicode_script::Script;
 
-use super::theme::{Bgra, Theme};
+use super::{
+    backend::{self, Backend},
+    composite,
+    config::{Blending, CalendarConfig, Config, FontConfig, FontStyle, FontsConfig, TodayStyle},
+    damage::{self, Rect},
+    theme::{Bgra, Theme},
+};
+
+// Under anything lifted off the background
+const SHADOW: Bgra = Bgra::from_rgba(0, 0, 0, 96);
+
+// The family name of the bundled variable Inter
+const INTER: &str = "Inter Variable";
+
+// Shaped text kept at most, the least recently drawn going first past it
+const LAYOUTS: usize = 128;
+
+// What a configured font lacks comes from Inter, then from the fonts the
+// platform falls back on; script-specific ones still go first for scripts
+// Inter doesn't cover
+struct InterFallback {
+    platform: PlatformFallback,
+    common: Vec<&'static str>,
+}
+
+impl InterFallback {
+    fn new() -> Self {
+        let platform = PlatformFallback;
+        let mut common = vec![INTER];
+        common.extend(platform.common_fallback());
+        Self { platform, common }
+    }
+}
+
+impl Fallback for InterFallback {
+    fn common_fallback(&self) -> &[&'static str] {
+        &self.common
+    }
+
+    fn forbidden_fallback(&self) -> &[&'static str] {
+        self.platform.forbidden_fallback()
+    }
+
+    fn script_fallback(&self, script: Script, locale: &str) -> &[&'static str] {
+        self.platform.script_fallback(script, locale)
+    }
+}
 
 pub struct Canvas {
     pub side: i32,
     radius: f32,
-    pub pixel_data: Vec<u8>,
     pub clock_bg_cache: Vec<u8>,
+    // The panel with a month's text, all but today's date
     pub calendar_bg_cache: Vec<u8>,
+    // The bare panel, to take days back out of the one above
+    calendar_panel_cache: Vec<u8>,
+    // The month in `calendar_bg_cache`, and the day left out of it as today
+    month: Option<(NaiveDate, Option<u32>)>,
+    pub world_bg_cache: Vec<u8>,
+    world_dials: Vec<Dial>,
     font_system: FontSystem,
     swash_cache: SwashCache,
+    // Each with when it was last drawn, counted in draws
+    layouts: HashMap<Layout, (Buffer, u64)>,
+    text_draws: u64,
     theme: Theme,
+    blending: Blending,
+    backend: Box<dyn Backend>,
+    calendar: CalendarConfig,
+    // Only naming families that were found
+    fonts: FontsConfig,
+    // Bumped whenever `calendar_bg_cache` is drawn again, the only background
+    // that changes
+    calendar_version: u64,
+    // The background under the last frame, and what was drawn over it
+    background: Option<(Background, u64)>,
+    last_drawn: Vec<Rect>,
+    // Drawn over the background since `clear`
+    drawn: Vec<Rect>,
+    // Changed on the surface since the last frame
+    damage: Vec<Rect>,
+    // Holds a frame while it's scaled and shifted into the target
+    scratch: Vec<u8>,
+}
+
+// What a buffer holds from the last frame drawn into it, so the next one
+// only has to restore the background where that one drew over it
+#[derive(Debug, Default)]
+pub struct Contents {
+    background: Option<(Background, u64)>,
+    drawn: Vec<Rect>,
+}
+
+// The static part a frame is drawn over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    Clock,
+    Calendar,
+    World,
+}
+
+// What a piece of text is, for the font it's set in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Text {
+    Month,
+    Weekday,
+    Day,
+    // Set in the day font, at least bold
+    Today,
+    Digits,
+    Label,
+}
+
+// Text as shaped; the fonts don't change, so its kind stands for them
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Layout {
+    kind: Text,
+    text: String,
+    // The bits of the `f32`s
+    font_size: u32,
+    width: u32,
+}
+
+// Where the day cells of a month sit on the canvas
+#[derive(Debug, Clone, Copy)]
+struct MonthGrid {
+    x: i32,
+    y: i32,
+    cell_width: f32,
+    cell_height: i32,
+    // Sunday is 0
+    start_weekday: i32,
+    days: i32,
+}
+
+// Where a clock face sits on the canvas
+#[derive(Debug, Clone, Copy)]
+struct Dial {
+    x: f32,
+    y: f32,
+    radius: f32,
+}
+
+impl MonthGrid {
+    // Top-left corner of a day's cell
+    fn cell(&self, day: i32) -> (i32, i32) {
+        let pos = self.start_weekday + day - 1;
+        (
+            self.x + (pos % 7) * self.cell_width as i32,
+            self.y + (pos / 7) * self.cell_height,
+        )
+    }
+
+    fn rect(&self, day: i32) -> Rect {
+        let (x, y) = self.cell(day);
+        Rect {
+            x,
+            y,
+            width: self.cell_width as i32,
+            height: self.cell_height,
+        }
+    }
+}
+
+impl Contents {
+    // Bytes the next `clear` copies to bring the buffer back to `background`
+    pub fn stale_bytes(&self, background: Background, canvas: &Canvas) -> usize {
+        if self.background == Some((background, canvas.version(background))) {
+            let area: i32 = self.drawn.iter().map(|r| r.width * r.height).sum();
+            area as usize * 4
+        } else {
+            (canvas.side * canvas.side * 4) as usize
+        }
+    }
 }
 
 impl Canvas {
-    pub fn new(side: i32) -> Self {
+    pub fn new(side: i32, config: &Config) -> Self {
         let theme = Theme::default();
+        let blending = config.surface.blending;
+        let backend = backend::new(config.surface.renderer, side, blending);
+        let calendar = config.calendar.clone();
 
         let radius = (side / 2) as f32;
-        let clock_bg_cache = Self::draw_clock_bg(side, radius, &theme);
-        let calendar_bg_cache = Self::draw_calendar_bg(side, &theme);
+        let clock_bg_cache = Self::draw_clock_bg(backend.as_ref(), side, radius, &theme);
+        let calendar_panel_cache =
+            Self::draw_calendar_bg(backend.as_ref(), side, &theme, &calendar);
+        // The local clock comes first in the world view
+        let world_dials = Self::world_layout(side, config.world.len() + 1);
+        let world_bg_cache = Self::draw_world_bg(backend.as_ref(), side, &world_dials, &theme);
 
-        let font = Arc::new(include_bytes!("../fonts/Inter-Regular.ttf"));
-        let mut font_db = Database::new();
-        font_db.load_font_source(Source::Binary(font));
+        let mut fonts = config.fonts.clone();
+        let font_db = Self::load_fonts(&mut fonts);
 
         Self {
             side,
             radius,
-            pixel_data: Self::new_buffer(side),
             clock_bg_cache,
-            calendar_bg_cache,
-            font_system: FontSystem::new_with_locale_and_db("en-US".into(), font_db),
+            calendar_bg_cache: calendar_panel_cache.clone(),
+            calendar_panel_cache,
+            month: None,
+            world_bg_cache,
+            world_dials,
+            font_system: FontSystem::new_with_locale_and_db_and_fallback(
+                "en-US".into(),
+                font_db,
+                InterFallback::new(),
+            ),
             swash_cache: SwashCache::new(),
+            layouts: HashMap::new(),
+            text_draws: 0,
             theme,
+            blending,
+            backend,
+            calendar,
+            fonts,
+            calendar_version: 0,
+            background: None,
+            last_drawn: Vec::new(),
+            drawn: Vec::new(),
+            damage: Vec::new(),
+            scratch: Vec::new(),
         }
     }
 
-    pub fn draw_clock_hands(&mut self, hour: u32, minute: u32, second: u32) {
-        self.draw_hour_hand(hour, minute, self.theme.primary);
-        self.draw_minute_hand(minute, self.theme.primary);
-        self.draw_second_hand(second, self.theme.secondary);
+    // Start a frame in a buffer from a background, restoring only what the
+    // last frame in it drew over when it's the same one
+    pub fn clear(&mut self, pixels: &mut [u8], contents: &mut Contents, background: Background) {
+        let cache = match background {
+            Background::Clock => &self.clock_bg_cache,
+            Background::Calendar => &self.calendar_bg_cache,
+            Background::World => &self.world_bg_cache,
+        };
+        let current = Some((background, self.version(background)));
+
+        if contents.background == current {
+            for rect in contents.drawn.drain(..) {
+                rect.copy(self.side, cache, pixels);
+            }
+        } else {
+            pixels.copy_from_slice(cache);
+            contents.drawn.clear();
+            contents.background = current;
+        }
+
+        // On the surface, what the last frame drew is gone, unless it's all new
+        self.damage = if self.background == current {
+            std::mem::take(&mut self.last_drawn)
+        } else {
+            vec![Rect::full(self.side)]
+        };
+        self.background = current;
+        self.drawn.clear();
+    }
+
+    // End the frame, noting in its buffer what was drawn; returns what
+    // changed on the surface
+    pub fn finish(&mut self, contents: &mut Contents) -> Vec<Rect> {
+        contents.drawn.clone_from(&self.drawn);
+
+        let mut damage = std::mem::take(&mut self.damage);
+        for rect in &self.drawn {
+            damage::add(&mut damage, *rect);
+        }
+        self.last_drawn = std::mem::take(&mut self.drawn);
+        damage
+    }
+
+    // Note a region drawn over the background
+    fn mark(&mut self, rect: Rect) {
+        if let Some(rect) = rect.clip(self.side) {
+            damage::add(&mut self.drawn, rect);
+        }
     }
 
-    fn draw_hour_hand(&mut self, hour: u32, minute: u32, color: Bgra) {
+    pub fn draw_clock_hands(&mut self, pixels: &mut [u8], hour: u32, minute: u32, second: u32) {
+        let dial = Dial {
+            x: self.radius,
+            y: self.radius,
+            radius: self.radius,
+        };
+        self.draw_hands(pixels, dial, hour, minute, second);
+    }
+
+    pub fn draw_clock_label(&mut self, pixels: &mut [u8], label: &str) {
+        let font_size = (self.radius * 0.12).ceil();
+        let y = (self.radius * 1.35) as i32;
+        self.draw_text(
+            pixels,
+            Text::Label,
+            label,
+            0,
+            y,
+            font_size,
+            self.side as f32,
+            self.theme.secondary,
+        );
+    }
+
+    fn draw_hands(&mut self, pixels: &mut [u8], dial: Dial, hour: u32, minute: u32, second: u32) {
+        self.draw_hour_hand(pixels, dial, hour, minute, self.theme.primary);
+        self.draw_minute_hand(pixels, dial, minute, self.theme.primary);
+        self.draw_second_hand(pixels, dial, second, self.theme.secondary);
+    }
+
+    fn draw_hour_hand(
+        &mut self,
+        pixels: &mut [u8],
+        dial: Dial,
+        hour: u32,
+        minute: u32,
+        color: Bgra,
+    ) {
         let angle = ((hour % 12) as f32 + minute as f32 / 60.0) * PI / 6.0 - PI / 2.0;
-        self.draw_thick_line_from_center(0.5, angle, 3.0, color);
+        self.draw_thick_line_from_center(pixels, dial, 0.5, angle, 3.0, color);
     }
 
-    fn draw_minute_hand(&mut self, minute: u32, color: Bgra) {
+    fn draw_minute_hand(&mut self, pixels: &mut [u8], dial: Dial, minute: u32, color: Bgra) {
         let angle = minute as f32 * PI / 30.0 - PI / 2.0;
-        self.draw_thick_line_from_center(0.8, angle, 2.0, color);
+        self.draw_thick_line_from_center(pixels, dial, 0.8, angle, 2.0, color);
     }
 
-    fn draw_second_hand(&mut self, second: u32, color: Bgra) {
+    fn draw_second_hand(&mut self, pixels: &mut [u8], dial: Dial, second: u32, color: Bgra) {
         let angle = second as f32 * PI / 30.0 - PI / 2.0;
-        self.draw_thick_line_from_center(0.9, angle, 0.7, color);
+        self.draw_thick_line_from_center(pixels, dial, 0.9, angle, 0.7, color);
     }
 
     fn draw_thick_line_from_center(
         &mut self,
+        pixels: &mut [u8],
+        dial: Dial,
         distance: f32,
         angle: f32,
         thickness: f32,
         color: Bgra,
     ) {
-        let end_x = self.radius + (self.radius * distance) * angle.cos();
-        let end_y = self.radius + (self.radius * distance) * angle.sin();
-
-        let dx = end_x - self.radius;
-        let dy = end_y - self.radius;
-        let steps = dx.abs().max(dy.abs()) as i32;
+        let end_x = dial.x + (dial.radius * distance) * angle.cos();
+        let end_y = dial.y + (dial.radius * distance) * angle.sin();
 
-        if steps == 0 {
+        // Clear of the center dot, counted in steps along the longer axis
+        let steps = (end_x - dial.x).abs().max((end_y - dial.y).abs()).floor();
+        let center_gap = 4.0 + (thickness / 2.0 + 2.0).ceil();
+        if steps < center_gap {
             return;
         }
+        let x = dial.x + (end_x - dial.x) * center_gap / steps;
+        let y = dial.y + (end_y - dial.y) * center_gap / steps;
+
+        // From where the hand starts to its tip, and as wide as it's drawn
+        let reach = (thickness / 2.0 + 3.0).ceil();
+        self.mark(Rect::around(
+            (x.min(end_x) - reach).floor() as i32,
+            (y.min(end_y) - reach).floor() as i32,
+            (x.max(end_x) + reach).ceil() as i32,
+            (y.max(end_y) + reach).ceil() as i32,
+        ));
+
+        self.backend
+            .stroke_line(pixels, (x, y), (end_x, end_y), thickness, color);
+    }
+
+    fn draw_clock_bg(backend: &dyn Backend, side: i32, radius: f32, theme: &Theme) -> Vec<u8> {
+        let mut buffer = Self::new_buffer(side);
+        let dial = Dial {
+            x: radius,
+            y: radius,
+            radius,
+        };
+        Self::draw_clock_face(backend, &mut buffer, dial, theme);
+        buffer
+    }
+
+    fn draw_clock_face(backend: &dyn Backend, buffer: &mut [u8], dial: Dial, theme: &Theme) {
+        let center = (dial.x, dial.y);
+        let frame_thickness = 2.0;
+
+        backend.stroke_circle(buffer, center, dial.radius, frame_thickness, theme.frame);
+        backend.fill_circle(
+            buffer,
+            center,
+            dial.radius - frame_thickness,
+            theme.background,
+        );
+        // Center dot
+        backend.fill_circle(buffer, center, 3.5, theme.highlight);
+    }
+
+    // A countdown, optionally as an arc filling the clock face
+    pub fn draw_timer(
+        &mut self,
+        pixels: &mut [u8],
+        progress: Option<f32>,
+        digits: &str,
+        label: &str,
+    ) {
+        let dial = Dial {
+            x: self.radius,
+            y: self.radius,
+            radius: self.radius,
+        };
+
+        // Keep clear of the center dot
+        let (digits_y, digits_size) = if let Some(progress) = progress {
+            self.draw_arc(pixels, dial, progress);
+            (self.radius * 1.15, self.radius * 0.2)
+        } else {
+            (self.radius * 0.55, self.radius * 0.35)
+        };
+        let label_y = (digits_y + digits_size * 1.3).max(self.radius * 1.1);
+
+        self.draw_text(
+            pixels,
+            Text::Digits,
+            digits,
+            0,
+            digits_y as i32,
+            digits_size.ceil(),
+            self.side as f32,
+            self.theme.primary,
+        );
+        self.draw_text(
+            pixels,
+            Text::Label,
+            label,
+            0,
+            label_y as i32,
+            (self.radius * 0.1).ceil(),
+            self.side as f32,
+            self.theme.secondary,
+        );
+    }
+
+    // Elapsed time above the center, the latest laps below it
+    pub fn draw_stopwatch(&mut self, pixels: &mut [u8], elapsed: &str, laps: &[String]) {
+        let elapsed_size = (self.radius * 0.3).ceil();
+        let lap_size = (self.radius * 0.1).ceil();
+
+        self.draw_text(
+            pixels,
+            Text::Digits,
+            elapsed,
+            0,
+            (self.radius * 0.6) as i32,
+            elapsed_size,
+            self.side as f32,
+            self.theme.primary,
+        );
+
+        for (i, lap) in laps.iter().enumerate() {
+            let y = self.radius * 1.15 + i as f32 * lap_size * 1.5;
+            self.draw_text(
+                pixels,
+                Text::Label,
+                lap,
+                0,
+                y as i32,
+                lap_size,
+                self.side as f32,
+                self.theme.secondary,
+            );
+        }
+    }
+
+    // Fill the face clockwise from 12 o'clock, with a hand at the edge
+    fn draw_arc(&mut self, pixels: &mut [u8], dial: Dial, progress: f32) {
+        let end = progress.clamp(0.0, 1.0) * 2.0 * PI;
 
-        let x_inc = dx / steps as f32;
-        let y_inc = dy / steps as f32;
-
-        let half_thickness = thickness / 2.0;
-        let search_radius = (half_thickness + 2.0).ceil() as i32;
-        let inner_radius = half_thickness - 1.0;
-        let outer_radius = half_thickness + 1.0;
-        let inner_radius_sq = inner_radius * inner_radius;
-        let outer_radius_sq = outer_radius * outer_radius;
-
-        let center_gap_radius = 4;
-        let skip_steps = center_gap_radius + search_radius;
-
-        let mut x = self.radius + x_inc * skip_steps as f32;
-        let mut y = self.radius + y_inc * skip_steps as f32;
-
-        for _ in skip_steps..=steps {
-            for dy_offset in -search_radius..=search_radius {
-                for dx_offset in -search_radius..=search_radius {
-                    let px = (x + dx_offset as f32).round() as i32;
-                    let py = (y + dy_offset as f32).round() as i32;
-
-                    if px >= 0 && px < self.side && py >= 0 && py < self.side {
-                        let squared_dist = Self::squared_distance(x, y, px as f32, py as f32);
-
-                        // Fade out at the edges
-                        let alpha = if squared_dist <= inner_radius_sq {
-                            1.0
-                        } else if squared_dist <= outer_radius_sq {
-                            1.0 - (squared_dist - inner_radius_sq)
-                                / (outer_radius_sq - inner_radius_sq)
-                        } else {
-                            continue;
-                        };
-
-                        Self::alpha_blending(
-                            &mut self.pixel_data,
-                            Self::pixel_idx(self.side, px, py),
-                            color,
-                            (alpha * 255.0) as u8,
-                        );
-                    }
+        self.fill_sector(
+            pixels,
+            dial,
+            0.0,
+            dial.radius - 2.0,
+            end,
+            self.theme.primary.fade(48),
+        );
+        self.draw_thick_line_from_center(
+            pixels,
+            dial,
+            0.9,
+            end - PI / 2.0,
+            2.0,
+            self.theme.highlight,
+        );
+    }
+
+    // A ring along the edge of the clock face, filled clockwise from 12
+    // o'clock
+    pub fn draw_progress_ring(&mut self, pixels: &mut [u8], progress: f32, on_break: bool) {
+        let dial = Dial {
+            x: self.radius,
+            y: self.radius,
+            radius: self.radius,
+        };
+        let end = progress.clamp(0.0, 1.0) * 2.0 * PI;
+        let color = if on_break {
+            self.theme.secondary
+        } else {
+            self.theme.highlight
+        };
+
+        self.fill_sector(
+            pixels,
+            dial,
+            dial.radius - 10.0,
+            dial.radius - 4.0,
+            end,
+            color.fade(200),
+        );
+    }
+
+    // Blend `color` over the part of the annulus between `inner` and `outer`
+    // that lies within `end` radians clockwise from 12 o'clock
+    fn fill_sector(
+        &mut self,
+        pixels: &mut [u8],
+        dial: Dial,
+        inner: f32,
+        outer: f32,
+        end: f32,
+        color: Bgra,
+    ) {
+        self.mark(Rect::around(
+            (dial.x - outer).floor() as i32,
+            (dial.y - outer).floor() as i32,
+            (dial.x + outer).ceil() as i32,
+            (dial.y + outer).ceil() as i32,
+        ));
+        self.backend
+            .fill_sector(pixels, (dial.x, dial.y), inner, outer, end, color);
+    }
+
+    // Tint everything that's drawn, to catch the eye
+    pub fn flash(&mut self, pixels: &mut [u8]) {
+        self.mark(Rect::full(self.side));
+        let tint = composite::premultiply(self.theme.highlight, 128);
+        for pixel in pixels.chunks_exact_mut(4) {
+            composite::atop(pixel, tint);
+        }
+    }
+
+    // Scale what's drawn about the center, shift it and fade it out, for a
+    // frame of the popup opening or closing
+    pub fn transition(&mut self, pixels: &mut [u8], scale: f32, offset: (i32, i32), opacity: f32) {
+        let opacity = (opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+        self.mark(Rect::full(self.side));
+
+        // Premultiplied, so every channel fades alike
+        if scale == 1.0 && offset == (0, 0) {
+            return composite::scale_span(pixels, opacity);
+        }
+
+        self.scratch.clear();
+        self.scratch.extend_from_slice(pixels);
+        pixels.fill(0);
+
+        let center = self.side as f32 / 2.0;
+
+        for y in 0..self.side {
+            for x in 0..self.side {
+                // Nearest pixel of the full size frame
+                let src_x = ((x - offset.0) as f32 + 0.5 - center) / scale + center;
+                let src_y = ((y - offset.1) as f32 + 0.5 - center) / scale + center;
+                if src_x < 0.0 || src_y < 0.0 {
+                    continue;
                 }
+                let (src_x, src_y) = (src_x as i32, src_y as i32);
+                if src_x >= self.side || src_y >= self.side {
+                    continue;
+                }
+
+                let src = Self::pixel_idx(self.side, src_x, src_y);
+                let dst = Self::pixel_idx(self.side, x, y);
+                pixels[dst..dst + 4].copy_from_slice(&self.scratch[src..src + 4]);
             }
-            x += x_inc;
-            y += y_inc;
         }
+
+        composite::scale_span(pixels, opacity);
     }
 
-    fn draw_clock_bg(side: i32, radius: f32, theme: &Theme) -> Vec<u8> {
-        let mut buffer = Self::new_buffer(side);
+    pub fn draw_world_clocks(&mut self, pixels: &mut [u8], clocks: &[(String, NaiveTime)]) {
+        let font_size = (self.world_dials[0].radius * 0.3).clamp(10.0, 24.0).ceil();
 
-        let radius_sq = radius * radius;
-        let bg_radius_sq = (radius - 2.0) * (radius - 2.0);
-
-        for y in 0..side {
-            for x in 0..side {
-                let dx = x as f32 - radius;
-                let dy = y as f32 - radius;
-                let center_dist_sq = dx * dx + dy * dy;
-
-                // Center dot
-                let color = if center_dist_sq < 12.0 {
-                    theme.highlight
-                // Background
-                } else if center_dist_sq <= bg_radius_sq {
-                    theme.background
-                // Frame
-                } else if center_dist_sq <= radius_sq {
-                    theme.frame
-                } else {
-                    continue; // Outside circle
-                };
+        for (i, (label, time)) in clocks.iter().enumerate() {
+            let Some(&dial) = self.world_dials.get(i) else {
+                break;
+            };
 
-                Self::set_pixel(&mut buffer, side, x, y, color);
-            }
+            self.draw_hands(pixels, dial, time.hour(), time.minute(), time.second());
+
+            let text = format!("{} {}", label, time.format("%H:%M"));
+            let width = dial.radius * 2.0 + font_size * 4.0;
+            let x = (dial.x - width / 2.0) as i32;
+            let y = (dial.y + dial.radius + font_size * 0.4) as i32;
+            self.draw_text(
+                pixels,
+                Text::Label,
+                &text,
+                x,
+                y,
+                font_size,
+                width,
+                self.theme.primary,
+            );
+        }
+    }
+
+    fn draw_world_bg(backend: &dyn Backend, side: i32, dials: &[Dial], theme: &Theme) -> Vec<u8> {
+        let mut buffer = Self::new_buffer(side);
+        Self::draw_panel(
+            backend,
+            &mut buffer,
+            Rect::full(side),
+            0.0,
+            2,
+            theme.frame,
+            theme.background,
+        );
+
+        for &dial in dials {
+            Self::draw_clock_face(backend, &mut buffer, dial, theme);
         }
         buffer
     }
 
+    fn world_layout(side: i32, count: usize) -> Vec<Dial> {
+        let count = count.max(1);
+        let cols = (count as f32).sqrt().ceil() as usize;
+        let rows = count.div_ceil(cols);
+
+        let padding = (side as f32 / 32.0).ceil();
+        let cell_width = (side as f32 - 2.0 * padding) / cols as f32;
+        let cell_height = (side as f32 - 2.0 * padding) / rows as f32;
+
+        // Leave room for the label below each face
+        let label_height = cell_height * 0.2;
+        let radius = (cell_width.min(cell_height - label_height) - padding) / 2.0;
+
+        (0..count)
+            .map(|i| {
+                let row = i / cols;
+                let col = i % cols;
+
+                // Center an incomplete last row
+                let in_row = cols.min(count - row * cols);
+                let offset = (cols - in_row) as f32 * cell_width / 2.0;
+
+                Dial {
+                    x: padding + offset + (col as f32 + 0.5) * cell_width,
+                    y: padding + row as f32 * cell_height + (cell_height - label_height) / 2.0,
+                    radius,
+                }
+            })
+            .collect()
+    }
+
     #[inline]
     fn pixel_idx(side: i32, x: i32, y: i32) -> usize {
         ((y * side + x) * 4) as usize
     }
 
-    #[inline]
-    fn squared_distance(x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
-        let dx = x2 - x1;
-        let dy = y2 - y1;
-        dx * dx + dy * dy
+    // Put a month in the calendar background, all but `today`; when only
+    // the day rolls over, just the old and the new today are redrawn
+    pub fn show_month(&mut self, first_of_month: NaiveDate, today: Option<u32>) {
+        let shown = Some((first_of_month, today));
+        if self.month == shown {
+            return;
+        }
+
+        let grid = Self::month_grid(self.side, self.calendar.shadow, first_of_month);
+        let mut cache = std::mem::take(&mut self.calendar_bg_cache);
+        match self.month {
+            Some((month, before)) if month == first_of_month => {
+                if let Some(day) = today {
+                    grid.rect(day as i32)
+                        .copy(self.side, &self.calendar_panel_cache, &mut cache);
+                }
+                if let Some(day) = before {
+                    self.draw_day(&mut cache, &grid, day as i32);
+                }
+            }
+            _ => {
+                cache.copy_from_slice(&self.calendar_panel_cache);
+                self.draw_month(&mut cache, first_of_month, &grid);
+                for day in (1..=grid.days).filter(|day| today != Some(*day as u32)) {
+                    self.draw_day(&mut cache, &grid, day);
+                }
+            }
+        }
+        self.calendar_bg_cache = cache;
+        self.month = shown;
+        self.calendar_version += 1;
     }
 
-    pub fn draw_calendar_fonts(&mut self, year: i32, month: u32, today: u32) {
-        // Calculate grid dimensions
-        let first_of_month = NaiveDate::from_ymd_opt(year, month, 1).expect("invalid date");
-        let start_weekday = first_of_month.weekday().num_days_from_sunday() as i32;
-        let days_in_month = first_of_month.num_days_in_month() as i32;
-        let rows_needed = (start_weekday + days_in_month + 6) / 7;
+    // Which drawing of a background a buffer was last cleared to
+    fn version(&self, background: Background) -> u64 {
+        match background {
+            Background::Calendar => self.calendar_version,
+            Background::Clock | Background::World => 0,
+        }
+    }
 
+    // The month's name and the weekdays over the grid
+    fn draw_month(&mut self, pixels: &mut [u8], first_of_month: NaiveDate, grid: &MonthGrid) {
         // Grid layout with 7 columns
-        let (padding, cell_width, cell_height, month_height) = Self::calendar_layout(self.side);
+        let (padding, cell_width, cell_height, month_height) =
+            Self::calendar_layout(self.side, self.calendar.shadow);
 
         let weekday_font_size = (cell_width * 0.4).ceil();
-        let day_font_size = (cell_width * 0.5).ceil();
 
         let month_header = first_of_month.format("%B %Y").to_string();
 
         // Calendar dimensions
         let total_width = cell_width as i32 * 7 + 2 * padding;
-        let total_height = 3 * padding + month_height + cell_height + rows_needed * cell_height;
 
         // Center on canvas
         let rect_x = (self.side - total_width) / 2;
-        let rect_y = (self.side - total_height) / 2;
+        let rect_y = grid.y - cell_height - month_height - 2 * padding;
 
         // Draw content relative to top-left, with padding
         let mut content_y = rect_y + padding;
 
         // Month name
-        self.draw_text(
+        self.render_text(
+            pixels,
+            Text::Month,
             &month_header,
             rect_x + padding,
             content_y,
@@ -216,7 +747,9 @@ impl Canvas {
         for (i, day_name) in weekdays.iter().enumerate() {
             let day_x = rect_x + padding + i as i32 * cell_width as i32;
             let day_y = content_y + (cell_height - day_header_height) / 2;
-            self.draw_text(
+            self.render_text(
+                pixels,
+                Text::Weekday,
                 day_name,
                 day_x,
                 day_y,
@@ -225,71 +758,180 @@ impl Canvas {
                 self.theme.secondary,
             );
         }
-        content_y += cell_height;
-
-        // Calendar grid
-        for day in 1..=days_in_month {
-            let day_pos = start_weekday + day - 1;
-            let row = day_pos / 7;
-            let col = day_pos % 7;
-
-            let day_str = day.to_string();
-            let is_today = today == day as u32;
-            let font_size = if is_today {
-                day_font_size + 6.0
-            } else {
-                day_font_size
-            };
+    }
 
-            let text_x = rect_x + padding + col * cell_width as i32;
-            let text_y =
-                content_y + row * cell_height + (cell_height - font_size.ceil() as i32) / 2;
-
-            if is_today {
-                // Bold + shadow effect
-                self.draw_text(
-                    &day_str,
-                    text_x + 1,
-                    text_y,
-                    font_size,
-                    cell_width,
-                    self.theme.secondary,
-                );
-                self.draw_text(
-                    &day_str,
-                    text_x,
-                    text_y - 1,
-                    font_size,
-                    cell_width,
-                    self.theme.highlight,
-                );
-                self.draw_text(
-                    &day_str,
-                    text_x - 1,
-                    text_y - 2,
-                    font_size,
-                    cell_width,
-                    self.theme.highlight,
-                );
-            } else {
-                self.draw_text(
-                    &day_str,
-                    text_x,
-                    text_y,
-                    font_size,
-                    cell_width,
-                    self.theme.primary,
-                );
+    // A date in its cell, as any day but today
+    fn draw_day(&mut self, pixels: &mut [u8], grid: &MonthGrid, day: i32) {
+        let day_font_size = (grid.cell_width * 0.5).ceil();
+        let (text_x, cell_y) = grid.cell(day);
+        let text_y = cell_y + (grid.cell_height - day_font_size as i32) / 2;
+        self.render_text(
+            pixels,
+            Text::Day,
+            &day.to_string(),
+            text_x,
+            text_y,
+            day_font_size,
+            grid.cell_width,
+            self.theme.primary,
+        );
+    }
+
+    // Mark today and the `selected` day over the month last shown
+    pub fn draw_calendar_fonts(&mut self, pixels: &mut [u8], selected: Option<u32>) {
+        let Some((first_of_month, today)) = self.month else {
+            return;
+        };
+        let grid = Self::month_grid(self.side, self.calendar.shadow, first_of_month);
+        let (cell_width, cell_height) = (grid.cell_width, grid.cell_height);
+        let day_font_size = (cell_width * 0.5).ceil();
+
+        if let Some(selected) = selected {
+            let cell = grid.rect(selected as i32);
+            self.mark(cell);
+            self.backend
+                .stroke_rect(pixels, cell, 0.0, 2.0, self.theme.secondary);
+        }
+
+        let Some(day) = today.map(|day| day as i32) else {
+            return;
+        };
+        let (text_x, cell_y) = grid.cell(day);
+        let day_str = day.to_string();
+
+        let style = self.calendar.today;
+        let font_size = match style {
+            TodayStyle::Bold => day_font_size + 6.0,
+            // Smaller, to fit inside
+            TodayStyle::Circle | TodayStyle::Ring => (day_font_size * 0.8).ceil(),
+            TodayStyle::Underline | TodayStyle::Inverted => day_font_size,
+        };
+        let text_y = cell_y + (cell_height - font_size as i32) / 2;
+
+        // Where the digits sit in their line, a little under its middle
+        let center = (
+            text_x as f32 + cell_width / 2.0,
+            text_y as f32 + font_size * 0.6,
+        );
+        let radius = cell_height as f32 / 2.0;
+        let disc = Rect::around(
+            (center.0 - radius).floor() as i32,
+            (center.1 - radius).floor() as i32,
+            (center.0 + radius).ceil() as i32,
+            (center.1 + radius).ceil() as i32,
+        );
+        let highlight = self.theme.highlight;
+
+        let (color, marker) = match style {
+            TodayStyle::Bold => (self.theme.highlight, None),
+            TodayStyle::Circle => {
+                self.backend.fill_circle(pixels, center, radius, highlight);
+                (self.theme.background, Some(disc))
+            }
+            TodayStyle::Ring => {
+                self.backend
+                    .stroke_circle(pixels, center, radius, 2.0, highlight);
+                (self.theme.highlight, Some(disc))
             }
+            TodayStyle::Underline => {
+                let bar = Rect {
+                    x: (center.0 - cell_width / 4.0) as i32,
+                    y: (center.1 + font_size * 0.5) as i32,
+                    width: (cell_width / 2.0) as i32,
+                    height: 2,
+                };
+                self.backend.fill_rect(pixels, bar, 1.0, highlight);
+                (self.theme.highlight, Some(bar))
+            }
+            TodayStyle::Inverted => {
+                let cell = grid.rect(day);
+                self.backend
+                    .fill_rect(pixels, cell, 0.0, self.theme.primary);
+                (self.theme.background, Some(cell))
+            }
+        };
+        if let Some(marker) = marker {
+            self.mark(marker);
+        }
+
+        self.draw_text(
+            pixels,
+            Text::Today,
+            &day_str,
+            text_x,
+            text_y,
+            font_size,
+            cell_width,
+            color,
+        );
+    }
+
+    // The day of the month under a point on the canvas
+    pub fn calendar_day_at(&self, first_of_month: NaiveDate, x: f64, y: f64) -> Option<u32> {
+        let grid = Self::month_grid(self.side, self.calendar.shadow, first_of_month);
+
+        let col = ((x - grid.x as f64) / grid.cell_width as f64).floor() as i32;
+        let row = ((y - grid.y as f64) / grid.cell_height as f64).floor() as i32;
+        let day = row * 7 + col - grid.start_weekday + 1;
+
+        ((0..7).contains(&col) && row >= 0 && (1..=grid.days).contains(&day)).then_some(day as u32)
+    }
+
+    fn month_grid(side: i32, shadow: u32, first_of_month: NaiveDate) -> MonthGrid {
+        let start_weekday = first_of_month.weekday().num_days_from_sunday() as i32;
+        let days = first_of_month.num_days_in_month() as i32;
+        let rows_needed = (start_weekday + days + 6) / 7;
+
+        let (padding, cell_width, cell_height, month_height) = Self::calendar_layout(side, shadow);
+        let total_width = cell_width as i32 * 7 + 2 * padding;
+        let total_height = 3 * padding + month_height + cell_height + rows_needed * cell_height;
+
+        MonthGrid {
+            x: (side - total_width) / 2 + padding,
+            // Below the month name and the weekday headers
+            y: (side - total_height) / 2 + 2 * padding + month_height + cell_height,
+            cell_width,
+            cell_height,
+            start_weekday,
+            days,
         }
     }
 
-    fn draw_calendar_bg(side: i32, theme: &Theme) -> Vec<u8> {
+    fn draw_calendar_bg(
+        backend: &dyn Backend,
+        side: i32,
+        theme: &Theme,
+        calendar: &CalendarConfig,
+    ) -> Vec<u8> {
         let mut buffer = Self::new_buffer(side);
+        let panel = Self::calendar_panel(side, calendar.shadow);
+        let radius = calendar.corner_radius as f32;
 
+        if calendar.shadow > 0 {
+            let drop = calendar.shadow as i32 / 4;
+            let below = Rect {
+                y: panel.y + drop,
+                ..panel
+            };
+            backend.shadow(&mut buffer, below, radius, calendar.shadow as f32, SHADOW);
+        }
+
+        Self::draw_panel(
+            backend,
+            &mut buffer,
+            panel,
+            radius,
+            calendar.border_width as i32,
+            calendar.border_color.unwrap_or(theme.frame),
+            theme.background,
+        );
+        buffer
+    }
+
+    // Where the calendar sits, tall enough for six weeks
+    fn calendar_panel(side: i32, shadow: u32) -> Rect {
         // Grid layout with 7 columns
-        let (padding, cell_width, cell_height, month_height) = Self::calendar_layout(side);
-        let frame_thickness = 2;
+        let (padding, cell_width, cell_height, month_height) = Self::calendar_layout(side, shadow);
 
         // Calendar dimensions
         let max_rows_needed = 6;
@@ -297,34 +939,44 @@ impl Canvas {
         let height = 3 * padding + month_height + cell_height + max_rows_needed * cell_height;
 
         // Center on canvas
-        let x = (side - width) / 2;
-        let y = (side - height) / 2;
-
-        // Draw frame
-        Self::fill_rect(&mut buffer, side, x, y, width, height, theme.frame);
-
-        // Draw background
-        Self::fill_rect(
-            &mut buffer,
-            side,
-            x + frame_thickness,
-            y + frame_thickness,
-            width - 2 * frame_thickness,
-            height - 2 * frame_thickness,
-            theme.background,
-        );
-        buffer
+        Rect {
+            x: (side - width) / 2,
+            y: (side - height) / 2,
+            width,
+            height,
+        }
     }
 
-    fn fill_rect(buffer: &mut [u8], side: i32, x: i32, y: i32, w: i32, h: i32, color: Bgra) {
-        for py in y.max(0)..(y + h).min(side) {
-            for px in x.max(0)..(x + w).min(side) {
-                Self::set_pixel(buffer, side, px, py, color);
-            }
+    // The background inside a border
+    fn draw_panel(
+        backend: &dyn Backend,
+        buffer: &mut [u8],
+        panel: Rect,
+        radius: f32,
+        border_width: i32,
+        border_color: Bgra,
+        background: Bgra,
+    ) {
+        if border_width > 0 {
+            backend.stroke_rect(buffer, panel, radius, border_width as f32, border_color);
         }
+        backend.fill_rect(
+            buffer,
+            Rect {
+                x: panel.x + border_width,
+                y: panel.y + border_width,
+                width: panel.width - 2 * border_width,
+                height: panel.height - 2 * border_width,
+            },
+            (radius - border_width as f32).max(0.0),
+            background,
+        );
     }
 
-    fn calendar_layout(side: i32) -> (i32, f32, i32, i32) {
+    fn calendar_layout(side: i32, shadow: u32) -> (i32, f32, i32, i32) {
+        // Room for the shadow, which falls a little below
+        let side = side - 2 * (shadow + shadow / 4) as i32;
+
         let padding = (side as f32 / 32.0).ceil() as i32;
         let width = ((side - 2 * padding) / 7) as f32;
         let height = (width * 0.7).ceil() as i32;
@@ -333,14 +985,104 @@ impl Canvas {
         (padding, width, height, month_height)
     }
 
-    fn draw_text(&mut self, text: &str, x: i32, y: i32, font_size: f32, width: f32, color: Bgra) {
-        let mut buffer = self.create_drawing_buffer(text, font_size, width);
-        // Convert BGRA to RGBA
-        let text_color = Color::rgba(color.r(), color.g(), color.b(), color.a());
+    // What the background under the last frame covers, to blur behind
+    pub fn backdrop(&self) -> Vec<Rect> {
+        match self.background {
+            Some((Background::Clock, _)) => Rect::full(self.side).rows(self.radius),
+            Some((Background::Calendar, _)) => {
+                Self::calendar_panel(self.side, self.calendar.shadow)
+                    .rows(self.calendar.corner_radius as f32)
+            }
+            Some((Background::World, _)) => vec![Rect::full(self.side)],
+            None => Vec::new(),
+        }
+    }
+
+    // The bundled Inter, variable so any weight is a real one, and the system
+    // fonts too when the config names any; families that aren't installed
+    // fall back to Inter
+    fn load_fonts(fonts: &mut FontsConfig) -> Database {
+        let inter = Arc::new(include_bytes!("../fonts/InterVariable.ttf"));
+        let mut font_db = Database::new();
+        font_db.load_font_source(Source::Binary(inter));
+
+        let fonts: [&mut FontConfig; 5] = [
+            &mut fonts.month,
+            &mut fonts.weekday,
+            &mut fonts.day,
+            &mut fonts.digits,
+            &mut fonts.label,
+        ];
+        if fonts.iter().any(|font| font.family.is_some()) {
+            font_db.load_system_fonts();
+        }
+
+        for font in fonts {
+            let Some(family) = &font.family else {
+                continue;
+            };
+            let installed = font_db.faces().any(|face| {
+                face.families
+                    .iter()
+                    .any(|(name, _)| name.eq_ignore_ascii_case(family))
+            });
+            if !installed {
+                eprintln!("Can't find the {} font, using Inter", family);
+                font.family = None;
+            }
+        }
+
+        font_db
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_text(
+        &mut self,
+        pixels: &mut [u8],
+        kind: Text,
+        text: &str,
+        x: i32,
+        y: i32,
+        font_size: f32,
+        width: f32,
+        color: Bgra,
+    ) {
+        if let Some(bounds) = self.render_text(pixels, kind, text, x, y, font_size, width, color) {
+            self.mark(bounds);
+        }
+    }
+
+    // Draw text without noting it, as into a cache; returns where it went
+    #[allow(clippy::too_many_arguments)]
+    fn render_text(
+        &mut self,
+        pixels: &mut [u8],
+        kind: Text,
+        text: &str,
+        x: i32,
+        y: i32,
+        font_size: f32,
+        width: f32,
+        color: Bgra,
+    ) -> Option<Rect> {
+        let layout = Layout {
+            kind,
+            text: text.to_string(),
+            font_size: font_size.to_bits(),
+            width: width.to_bits(),
+        };
+        let mut buffer = match self.layouts.remove(&layout) {
+            Some((buffer, _)) => buffer,
+            None => self.create_drawing_buffer(kind, text, font_size, width),
+        };
+
+        // Glyphs come as coverage alone, the color's alpha is blended in after
+        let text_color = Color::rgba(color.r(), color.g(), color.b(), 255);
 
         // Capture needed fields to avoid borrow issues
         let side = self.side;
-        let pixel_data = &mut self.pixel_data;
+        let blending = self.blending;
+        let mut bounds: Option<Rect> = None;
 
         buffer.draw(
             &mut self.font_system,
@@ -352,51 +1094,135 @@ impl Canvas {
 
                 if px >= 0 && px < side && py >= 0 && py < side {
                     Self::alpha_blending(
-                        pixel_data,
+                        pixels,
                         Self::pixel_idx(side, px, py),
                         color,
                         glyph_color.a(),
+                        blending,
                     );
+
+                    let pixel = Rect::around(px, py, px, py);
+                    bounds = Some(bounds.map_or(pixel, |b| b.union(&pixel)));
                 }
             },
         );
+
+        if self.layouts.len() >= LAYOUTS
+            && let Some(stale) = self
+                .layouts
+                .iter()
+                .min_by_key(|(_, (_, drawn))| *drawn)
+                .map(|(layout, _)| layout.clone())
+        {
+            self.layouts.remove(&stale);
+        }
+        self.text_draws += 1;
+        self.layouts.insert(layout, (buffer, self.text_draws));
+        bounds
     }
 
-    fn create_drawing_buffer(&mut self, text: &str, font_size: f32, width: f32) -> Buffer {
+    fn create_drawing_buffer(
+        &mut self,
+        kind: Text,
+        text: &str,
+        font_size: f32,
+        width: f32,
+    ) -> Buffer {
+        let font = match kind {
+            Text::Month => &self.fonts.month,
+            Text::Weekday => &self.fonts.weekday,
+            Text::Day | Text::Today => &self.fonts.day,
+            Text::Digits => &self.fonts.digits,
+            Text::Label => &self.fonts.label,
+        };
+        let weight = match kind {
+            Text::Today => font.weight.max(Weight::BOLD.0),
+            _ => font.weight,
+        };
+        let attrs = Attrs::new()
+            .family(Family::Name(font.family.as_deref().unwrap_or(INTER)))
+            .weight(Weight(weight))
+            .style(match font.style {
+                FontStyle::Normal => Style::Normal,
+                FontStyle::Italic => Style::Italic,
+                FontStyle::Oblique => Style::Oblique,
+            });
+
         let metrics = Metrics::new(font_size, font_size * 1.2);
         let mut buffer = Buffer::new(&mut self.font_system, metrics);
         buffer.set_size(Some(width), Some(self.side as f32));
-        buffer.set_text(text, &Attrs::new(), Shaping::Advanced, Some(Align::Center));
+        buffer.set_text(text, &attrs, Shaping::Advanced, Some(Align::Center));
         buffer.shape_until_scroll(&mut self.font_system, false);
         buffer
     }
 
-    fn set_pixel(buffer: &mut [u8], side: i32, x: i32, y: i32, color: Bgra) {
-        let index = Self::pixel_idx(side, x, y);
-        if index + 3 < buffer.len() {
-            buffer[index..index + 4].copy_from_slice(color.as_ref());
+    fn alpha_blending(pxl_data: &mut [u8], idx: usize, color: Bgra, alpha: u8, blending: Blending) {
+        if let Some(pixel) = pxl_data.get_mut(idx..idx + 4) {
+            composite::blend(pixel, color, alpha, blending);
         }
     }
 
-    fn alpha_blending(pxl_data: &mut [u8], idx: usize, color: Bgra, alpha: u8) {
-        if idx + 3 >= pxl_data.len() {
-            return;
-        }
+    #[inline]
+    fn new_buffer(side: i32) -> Vec<u8> {
+        vec![0u8; (side * side * 4) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Background, Canvas, Contents, LAYOUTS, Text};
+    use crate::{config::Config, theme::Bgra};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn day_rollover_matches_redraw() {
+        let october = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
 
-        let inv_alpha = 255 - alpha;
+        let mut rolled = Canvas::new(256, &Config::default());
+        rolled.show_month(october, Some(17));
+        let version = rolled.calendar_version;
+        rolled.show_month(october, Some(18));
+        assert_eq!(rolled.calendar_version, version + 1);
 
-        pxl_data[idx] = Self::blend_color(color.b(), alpha, pxl_data[idx], inv_alpha);
-        pxl_data[idx + 1] = Self::blend_color(color.g(), alpha, pxl_data[idx + 1], inv_alpha);
-        pxl_data[idx + 2] = Self::blend_color(color.r(), alpha, pxl_data[idx + 2], inv_alpha);
+        let mut fresh = Canvas::new(256, &Config::default());
+        fresh.show_month(october, Some(18));
+        assert!(rolled.calendar_bg_cache == fresh.calendar_bg_cache);
+
+        // Nothing to redraw for the same day
+        rolled.show_month(october, Some(18));
+        assert_eq!(rolled.calendar_version, version + 1);
     }
 
-    #[inline]
-    fn new_buffer(side: i32) -> Vec<u8> {
-        vec![0u8; (side * side * 4) as usize]
+    #[test]
+    fn month_change_keeps_other_backgrounds() {
+        let mut canvas = Canvas::new(256, &Config::default());
+        let mut pixels = vec![0; 256 * 256 * 4];
+        let mut contents = Contents::default();
+        canvas.clear(&mut pixels, &mut contents, Background::Clock);
+        canvas.draw_clock_hands(&mut pixels, 10, 10, 0);
+        canvas.finish(&mut contents);
+
+        let stale = contents.stale_bytes(Background::Clock, &canvas);
+        canvas.show_month(NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(), None);
+        assert_eq!(contents.stale_bytes(Background::Clock, &canvas), stale);
+        assert!(stale < pixels.len());
     }
 
-    #[inline]
-    fn blend_color(src: u8, alpha: u8, dst: u8, inv_alpha: u8) -> u8 {
-        ((src as u16 * alpha as u16 + dst as u16 * inv_alpha as u16) >> 8) as u8
+    #[test]
+    fn labels_drawn_every_frame_stay_shaped() {
+        let mut canvas = Canvas::new(256, &Config::default());
+        let mut pixels = vec![0; 256 * 256 * 4];
+        let color = Bgra::from_rgba(255, 255, 255, 255);
+        let mut draw = |canvas: &mut Canvas, text: &str| {
+            canvas.render_text(&mut pixels, Text::Label, text, 0, 0, 12.0, 256.0, color);
+        };
+
+        // A ticking stopwatch under a label that stays
+        for tenth in 0..2 * LAYOUTS {
+            draw(&mut canvas, "lap");
+            draw(&mut canvas, &format!("0:{:04.1}", tenth as f32 / 10.0));
+        }
+        assert_eq!(canvas.layouts.len(), LAYOUTS);
+        assert!(canvas.layouts.keys().any(|layout| layout.text == "lap"));
     }
 }
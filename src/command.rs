@@ -1,41 +1,468 @@
-use crate::{Event, View};
+use chrono::{NaiveTime, Weekday};
+use chrono_tz::Tz;
+use std::time::Duration;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use crate::{
+    Event, View,
+    alarm::{format_days, parse_days},
+    pomodoro::PomodoroAction,
+    stopwatch::StopwatchAction,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
-    Clock,
+    Clock(Option<Tz>),
     Calendar,
+    World,
+    // Toggle the timer view
+    Timer,
+    StartTimer {
+        duration: Duration,
+        name: Option<String>,
+    },
+    CancelTimer(String),
+    ListTimers,
+    // Toggle the stopwatch view, or drive the stopwatch
+    Stopwatch(Option<StopwatchAction>),
+    AddAlarm {
+        time: NaiveTime,
+        days: Vec<Weekday>,
+        label: Option<String>,
+    },
+    RemoveAlarm(u32),
+    ListAlarms,
+    Pomodoro(PomodoroAction),
+    Status,
+    // Keep the connection open for a stream of notices
+    Subscribe,
+    Show(View),
+    Hide,
+    Switch(View),
     Dismiss,
 }
 
+// A command along with the options that apply to any of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub command: Command,
+    // Close the popup this opens after a while, overriding the config
+    pub timeout: Option<Duration>,
+    // Close the popup this opens once the keys held to open it are released
+    pub peek: bool,
+}
+
+impl Invocation {
+    pub fn from_args<'a>(args: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut words: Vec<&str> = args.into_iter().collect();
+        let mut timeout = None;
+        let mut peek = false;
+
+        // Only after the command, so a name or label may look like a flag
+        loop {
+            match words.as_slice() {
+                [.., "--peek"] => peek = true,
+                [.., "--timeout", value] => {
+                    timeout = Some(parse_duration(value)?);
+                    words.pop();
+                }
+                [.., "--timeout"] => return Err("--timeout needs a duration".into()),
+                _ => break,
+            }
+            words.pop();
+        }
+
+        let command = Command::from_args(words)?;
+        let opens = matches!(
+            command.event(),
+            Some(Event::Toggle(_) | Event::Show(_) | Event::Switch(_))
+        );
+        if (timeout.is_some() || peek) && !opens {
+            return Err("--timeout and --peek only go with a command showing a view".into());
+        }
+
+        Ok(Self {
+            command,
+            timeout,
+            peek,
+        })
+    }
+
+    pub fn args(&self) -> Vec<String> {
+        let mut args = self.command.args();
+        if let Some(timeout) = self.timeout {
+            args.extend(["--timeout".into(), format_duration(timeout)]);
+        }
+        if self.peek {
+            args.push("--peek".into());
+        }
+        args
+    }
+}
+
+impl From<Command> for Invocation {
+    fn from(command: Command) -> Self {
+        Self {
+            command,
+            timeout: None,
+            peek: false,
+        }
+    }
+}
+
 impl std::str::FromStr for Command {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "clock" => Ok(Self::Clock),
-            "calendar" => Ok(Self::Calendar),
-            "dismiss" => Ok(Self::Dismiss),
-            _ => Err(format!("Unknown command: {}", s)),
-        }
+        Self::from_args(s.split_whitespace())
     }
 }
 
-impl std::fmt::Display for Command {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Command {
+    // Parse the command from separate words, so arguments may hold spaces
+    pub fn from_args<'a>(args: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut words = args.into_iter();
+
+        let command = match words.next().unwrap_or_default() {
+            "clock" => match (words.next(), words.next()) {
+                (None, _) => Self::Clock(None),
+                (Some("--tz"), Some(tz)) => Self::Clock(Some(
+                    tz.parse()
+                        .map_err(|_| format!("Unknown time zone: {}", tz))?,
+                )),
+                (Some("--tz"), None) => return Err("--tz needs a time zone".into()),
+                (Some(arg), _) => return Err(format!("Unknown argument: {}", arg)),
+            },
+            "calendar" => Self::Calendar,
+            "world" => Self::World,
+            "timer" => match words.next() {
+                None => Self::Timer,
+                Some("list") => Self::ListTimers,
+                Some("cancel") => match words.next() {
+                    Some(name) => Self::CancelTimer(name.into()),
+                    None => return Err("cancel needs a timer name".into()),
+                },
+                Some(duration) => Self::StartTimer {
+                    duration: parse_duration(duration)?,
+                    name: match (words.next(), words.next()) {
+                        (None, _) => None,
+                        (Some("--name"), Some(name)) => Some(name.into()),
+                        (Some("--name"), None) => return Err("--name needs a name".into()),
+                        (Some(arg), _) => return Err(format!("Unknown argument: {}", arg)),
+                    },
+                },
+            },
+            "stopwatch" => match words.next() {
+                None => Self::Stopwatch(None),
+                Some(action) => Self::Stopwatch(Some(action.parse()?)),
+            },
+            "alarm" => match words.next() {
+                Some("add") => {
+                    let time = words.next().ok_or("add needs a time")?;
+                    let time = NaiveTime::parse_from_str(time, "%H:%M")
+                        .map_err(|_| format!("Invalid time: {}", time))?;
+                    let mut days = Vec::new();
+                    let mut label = None;
+
+                    while let Some(arg) = words.next() {
+                        match (arg, words.next()) {
+                            ("--days", Some(value)) => days = parse_days(value)?,
+                            ("--label", Some(value)) => label = Some(value.into()),
+                            ("--days" | "--label", None) => {
+                                return Err(format!("{} needs a value", arg));
+                            }
+                            _ => return Err(format!("Unknown argument: {}", arg)),
+                        }
+                    }
+
+                    Self::AddAlarm { time, days, label }
+                }
+                Some("remove") => {
+                    let id = words.next().ok_or("remove needs an alarm number")?;
+                    Self::RemoveAlarm(id.parse().map_err(|_| format!("Invalid alarm: {}", id))?)
+                }
+                Some("list") => Self::ListAlarms,
+                _ => return Err("alarm needs add, remove or list".into()),
+            },
+            "pomodoro" => match words.next() {
+                Some(action) => Self::Pomodoro(action.parse()?),
+                None => return Err("pomodoro needs start, pause, skip or status".into()),
+            },
+            "status" => Self::Status,
+            "subscribe" => Self::Subscribe,
+            "show" => Self::Show(words.next().ok_or("show needs a view")?.parse()?),
+            "hide" => Self::Hide,
+            "switch" => Self::Switch(words.next().ok_or("switch needs a view")?.parse()?),
+            "dismiss" => Self::Dismiss,
+            word => return Err(format!("Unknown command: {}", word)),
+        };
+
+        match words.next() {
+            Some(arg) => Err(format!("Unknown argument: {}", arg)),
+            None => Ok(command),
+        }
+    }
+
+    // The words `from_args` parses back into the same command
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Self::Clock(None) => vec!["clock".into()],
+            Self::Clock(Some(tz)) => vec!["clock".into(), "--tz".into(), tz.name().into()],
+            Self::Calendar => vec!["calendar".into()],
+            Self::World => vec!["world".into()],
+            Self::Timer => vec!["timer".into()],
+            Self::StartTimer { duration, name } => {
+                let mut args = vec!["timer".into(), format_duration(*duration)];
+                if let Some(name) = name {
+                    args.extend(["--name".into(), name.clone()]);
+                }
+                args
+            }
+            Self::CancelTimer(name) => vec!["timer".into(), "cancel".into(), name.clone()],
+            Self::ListTimers => vec!["timer".into(), "list".into()],
+            Self::Stopwatch(None) => vec!["stopwatch".into()],
+            Self::Stopwatch(Some(action)) => vec!["stopwatch".into(), action.to_string()],
+            Self::AddAlarm { time, days, label } => {
+                let mut args = vec![
+                    "alarm".into(),
+                    "add".into(),
+                    time.format("%H:%M").to_string(),
+                ];
+                if !days.is_empty() {
+                    args.extend(["--days".into(), format_days(days)]);
+                }
+                if let Some(label) = label {
+                    args.extend(["--label".into(), label.clone()]);
+                }
+                args
+            }
+            Self::RemoveAlarm(id) => vec!["alarm".into(), "remove".into(), id.to_string()],
+            Self::ListAlarms => vec!["alarm".into(), "list".into()],
+            Self::Pomodoro(action) => vec!["pomodoro".into(), action.to_string()],
+            Self::Status => vec!["status".into()],
+            Self::Subscribe => vec!["subscribe".into()],
+            Self::Show(view) => vec!["show".into(), view.to_string()],
+            Self::Hide => vec!["hide".into()],
+            Self::Switch(view) => vec!["switch".into(), view.to_string()],
+            Self::Dismiss => vec!["dismiss".into()],
+        }
+    }
+
+    // The lifecycle event behind the command, if it affects the surface
+    pub fn event(&self) -> Option<Event> {
         match self {
-            Self::Clock => write!(f, "clock"),
-            Self::Calendar => write!(f, "calendar"),
-            Self::Dismiss => write!(f, "dismiss"),
+            Self::Clock(_) => Some(Event::Toggle(View::Clock)),
+            Self::Calendar => Some(Event::Toggle(View::Calendar)),
+            Self::World => Some(Event::Toggle(View::World)),
+            Self::Timer => Some(Event::Toggle(View::Timer)),
+            Self::Stopwatch(None) => Some(Event::Toggle(View::Stopwatch)),
+            Self::Show(view) => Some(Event::Show(*view)),
+            Self::Hide => Some(Event::Hide),
+            Self::Switch(view) => Some(Event::Switch(*view)),
+            Self::Dismiss => Some(Event::Quit),
+            Self::StartTimer { .. }
+            | Self::CancelTimer(_)
+            | Self::ListTimers
+            | Self::Stopwatch(Some(_))
+            | Self::AddAlarm { .. }
+            | Self::RemoveAlarm(_)
+            | Self::ListAlarms
+            | Self::Pomodoro(_)
+            | Self::Status
+            | Self::Subscribe => None,
         }
     }
 }
 
-impl From<Command> for Event {
-    fn from(cmd: Command) -> Self {
-        match cmd {
-            Command::Clock => Event::Toggle(View::Clock),
-            Command::Calendar => Event::Toggle(View::Calendar),
-            Command::Dismiss => Event::Quit,
+// Longest a timer or a phase can run, a year
+const MAX_DURATION: u64 = 365 * 24 * 3600;
+
+// `90s`, `25m`, `1h30m`, up to a year
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {}", s);
+
+    let mut secs = 0u64;
+    let mut digits = String::new();
+
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = digits.parse().map_err(|_| invalid())?;
+        secs = value
+            .checked_mul(unit)
+            .and_then(|value| secs.checked_add(value))
+            .filter(|secs| *secs <= MAX_DURATION)
+            .ok_or_else(|| format!("Duration too long: {}", s))?;
+        digits.clear();
+    }
+
+    if !digits.is_empty() || secs == 0 {
+        return Err(invalid());
+    }
+
+    Ok(Duration::from_secs(secs))
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+
+    [(h, 'h'), (m, 'm'), (s, 's')]
+        .iter()
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Invocation, format_duration, parse_duration};
+    use crate::{View, pomodoro::PomodoroAction, stopwatch::StopwatchAction};
+    use chrono::{NaiveTime, Weekday};
+    use std::time::Duration;
+
+    #[test]
+    fn parse_clock_with_time_zone() {
+        assert_eq!("clock".parse(), Ok(Command::Clock(None)));
+        assert_eq!(
+            "clock --tz Asia/Tokyo".parse(),
+            Ok(Command::Clock(Some(chrono_tz::Asia::Tokyo)))
+        );
+        assert!("clock --tz".parse::<Command>().is_err());
+        assert!("clock --tz Mars/Olympus".parse::<Command>().is_err());
+        assert!("clock --tz Asia/Tokyo now".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn args_round_trip() {
+        for cmd in [
+            Command::Clock(None),
+            Command::Clock(Some(chrono_tz::America::Sao_Paulo)),
+            Command::Calendar,
+            Command::World,
+            Command::Timer,
+            Command::StartTimer {
+                duration: Duration::from_secs(90),
+                name: None,
+            },
+            Command::StartTimer {
+                duration: Duration::from_secs(25 * 60),
+                name: Some("tea".into()),
+            },
+            Command::CancelTimer("tea".into()),
+            Command::ListTimers,
+            Command::Stopwatch(None),
+            Command::Stopwatch(Some(StopwatchAction::Lap)),
+            Command::AddAlarm {
+                time: NaiveTime::from_hms_opt(7, 30, 0).unwrap(),
+                days: vec![Weekday::Mon, Weekday::Fri],
+                label: Some("team standup".into()),
+            },
+            Command::AddAlarm {
+                time: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+                days: vec![],
+                label: None,
+            },
+            Command::RemoveAlarm(3),
+            Command::ListAlarms,
+            Command::Pomodoro(PomodoroAction::Skip),
+            Command::Status,
+            Command::Subscribe,
+            Command::Show(View::Calendar),
+            Command::Hide,
+            Command::Switch(View::Stopwatch),
+            Command::Dismiss,
+        ] {
+            let args = cmd.args();
+            assert_eq!(Command::from_args(args.iter().map(String::as_str)), Ok(cmd));
         }
     }
+
+    #[test]
+    fn parse_timeout() {
+        let invocation = Invocation::from_args(["calendar", "--timeout", "5s", "--peek"]).unwrap();
+        assert_eq!(invocation.command, Command::Calendar);
+        assert_eq!(invocation.timeout, Some(Duration::from_secs(5)));
+        assert!(invocation.peek);
+
+        let args = invocation.args();
+        assert_eq!(
+            Invocation::from_args(args.iter().map(String::as_str)),
+            Ok(invocation)
+        );
+
+        assert!(Invocation::from_args(["clock", "--timeout"]).is_err());
+        assert!(Invocation::from_args(["clock", "--timeout", "0s"]).is_err());
+        assert!(Invocation::from_args(["--peek", "calendar"]).is_err());
+        assert!(Invocation::from_args(["status", "--timeout", "5s"]).is_err());
+        assert!(Invocation::from_args(["timer", "5m", "--peek"]).is_err());
+    }
+
+    #[test]
+    fn parse_timer() {
+        assert_eq!(
+            "timer 25m --name pasta".parse(),
+            Ok(Command::StartTimer {
+                duration: Duration::from_secs(25 * 60),
+                name: Some("pasta".into())
+            })
+        );
+        assert!("timer 25m --name".parse::<Command>().is_err());
+        assert!("timer cancel".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn parse_alarm() {
+        assert_eq!(
+            "alarm add 07:30 --days mon-fri --label standup".parse(),
+            Ok(Command::AddAlarm {
+                time: NaiveTime::from_hms_opt(7, 30, 0).unwrap(),
+                days: vec![
+                    Weekday::Mon,
+                    Weekday::Tue,
+                    Weekday::Wed,
+                    Weekday::Thu,
+                    Weekday::Fri
+                ],
+                label: Some("standup".into()),
+            })
+        );
+        assert!("alarm add".parse::<Command>().is_err());
+        assert!("alarm add 7:30pm".parse::<Command>().is_err());
+        assert!("alarm add 07:30 --days".parse::<Command>().is_err());
+        assert!("alarm remove one".parse::<Command>().is_err());
+        assert!("alarm".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("25").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("5d").is_err());
+    }
+
+    #[test]
+    fn parse_duration_overflow() {
+        assert_eq!(parse_duration("8760h"), Ok(Duration::from_secs(31_536_000)));
+        assert!(parse_duration("8760h1s").is_err());
+        assert!(parse_duration("99999999999999999h").is_err());
+        assert!(parse_duration("10000000000000000000s").is_err());
+        assert!(parse_duration("18446744073709551615s1s").is_err());
+        assert!(parse_duration("99999999999999999999s").is_err());
+
+        assert_eq!(format_duration(Duration::from_secs(90)), "1m30s");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h");
+    }
 }
@@ -0,0 +1,403 @@
+// Blending BGRA pixels, premultiplied as `wl_shm` expects them, with a
+// vectorized path for whole spans where the target has one
+
+use std::sync::LazyLock;
+
+use crate::{config::Blending, theme::Bgra};
+
+// `x / 255` rounded to the nearest, exact for any product of two bytes
+#[inline]
+pub fn div255(x: u32) -> u8 {
+    let x = x + 128;
+    ((x + (x >> 8)) >> 8) as u8
+}
+
+// A straight alpha color at some coverage, premultiplied, to composite
+// with `over`
+#[inline]
+pub fn premultiply(color: Bgra, coverage: u8) -> [u8; 4] {
+    let alpha = div255(color.a() as u32 * coverage as u32);
+
+    [
+        div255(color.b() as u32 * alpha as u32),
+        div255(color.g() as u32 * alpha as u32),
+        div255(color.r() as u32 * alpha as u32),
+        alpha,
+    ]
+}
+
+// Composite a straight alpha color at some coverage over a pixel
+#[inline]
+pub fn blend(pixel: &mut [u8], color: Bgra, coverage: u8, blending: Blending) {
+    match blending {
+        Blending::Srgb => over(pixel, premultiply(color, coverage)),
+        Blending::Linear => over_linear(pixel, color, coverage),
+    }
+}
+
+// `blend` for every pixel of a span
+pub fn blend_span(span: &mut [u8], color: Bgra, coverage: u8, blending: Blending) {
+    match blending {
+        Blending::Srgb => over_span(span, premultiply(color, coverage)),
+        Blending::Linear => {
+            for pixel in span.chunks_exact_mut(4) {
+                over_linear(pixel, color, coverage);
+            }
+        }
+    }
+}
+
+// Composite a premultiplied pixel over another
+#[inline]
+pub fn over(pixel: &mut [u8], src: [u8; 4]) {
+    let inv_alpha = 255 - src[3] as u32;
+
+    for (dst, src) in pixel.iter_mut().zip(src) {
+        *dst = src.saturating_add(div255(*dst as u32 * inv_alpha));
+    }
+}
+
+// Composite a premultiplied pixel only over what's already there, keeping
+// the destination's alpha
+#[inline]
+pub fn atop(pixel: &mut [u8], src: [u8; 4]) {
+    let dst_alpha = pixel[3] as u32;
+    let inv_alpha = 255 - src[3] as u32;
+
+    for (dst, src) in pixel[..3].iter_mut().zip(src) {
+        *dst = div255(src as u32 * dst_alpha + *dst as u32 * inv_alpha);
+    }
+}
+
+// `over` with the color channels mixed in linear light, so antialiased
+// edges keep their weight instead of looking thin and dark
+pub fn over_linear(pixel: &mut [u8], color: Bgra, coverage: u8) {
+    let alpha = div255(color.a() as u32 * coverage as u32) as u32;
+    let dst_alpha = pixel[3] as u32;
+    // How much of the destination still shows through
+    let behind = div255(dst_alpha * (255 - alpha)) as u32;
+    let out_alpha = alpha + behind;
+
+    if out_alpha == 0 {
+        return;
+    }
+
+    let (to_linear, to_srgb) = (&*TO_LINEAR, &*TO_SRGB);
+    for (dst, src) in pixel[..3].iter_mut().zip([color.b(), color.g(), color.r()]) {
+        // Straight alpha again, the curve applies to colors, not coverage
+        let straight = (*dst as u32 * 255 + dst_alpha / 2)
+            .checked_div(dst_alpha)
+            .unwrap_or(0)
+            .min(255);
+
+        let linear = (to_linear[src as usize] as u32 * alpha
+            + to_linear[straight as usize] as u32 * behind
+            + out_alpha / 2)
+            / out_alpha;
+        *dst = div255(to_srgb[linear as usize] as u32 * out_alpha);
+    }
+    pixel[3] = out_alpha as u8;
+}
+
+// sRGB bytes to 16 bit linear light, and back
+static TO_LINEAR: LazyLock<[u16; 256]> = LazyLock::new(|| {
+    std::array::from_fn(|i| {
+        let c = i as f64 / 255.0;
+        let linear = if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        };
+        (linear * 65535.0).round() as u16
+    })
+});
+
+static TO_SRGB: LazyLock<Box<[u8]>> = LazyLock::new(|| {
+    (0..=u16::MAX)
+        .map(|i| {
+            let linear = i as f64 / 65535.0;
+            let c = if linear <= 0.0031308 {
+                linear * 12.92
+            } else {
+                1.055 * linear.powf(1.0 / 2.4) - 0.055
+            };
+            (c * 255.0).round() as u8
+        })
+        .collect()
+});
+
+// Composite a premultiplied color over every pixel of a span
+pub fn over_span(span: &mut [u8], src: [u8; 4]) {
+    let done = simd::over_span(span, src);
+    scalar::over_span(&mut span[done..], src);
+}
+
+// Multiply every channel of a span by `factor / 255`, fading it out
+pub fn scale_span(span: &mut [u8], factor: u8) {
+    let done = simd::scale_span(span, factor);
+    scalar::scale_span(&mut span[done..], factor);
+}
+
+// The reference the vectorized paths must match bit for bit
+mod scalar {
+    use super::div255;
+
+    pub fn over_span(span: &mut [u8], src: [u8; 4]) {
+        for pixel in span.chunks_exact_mut(4) {
+            super::over(pixel, src);
+        }
+    }
+
+    pub fn scale_span(span: &mut [u8], factor: u8) {
+        for channel in span {
+            *channel = div255(*channel as u32 * factor as u32);
+        }
+    }
+}
+
+// Both return how many bytes they did, whole blocks of 4 pixels, leaving
+// the rest to the scalar path
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use std::arch::x86_64::*;
+
+    // SSE2 is part of x86_64, no need to check for it at runtime
+    pub fn over_span(span: &mut [u8], src: [u8; 4]) -> usize {
+        let len = span.len() / 16 * 16;
+        let inv_alpha = 255 - src[3] as i16;
+
+        // SAFETY: SSE2 is always there on x86_64, and every load and store
+        // is unaligned and within the first `len` bytes
+        unsafe {
+            let src = _mm_set1_epi32(i32::from_ne_bytes(src));
+            let inv_alpha = _mm_set1_epi16(inv_alpha);
+
+            for block in span[..len].chunks_exact_mut(16) {
+                let ptr = block.as_mut_ptr() as *mut __m128i;
+                let dst = _mm_loadu_si128(ptr);
+                let scaled = scale(dst, inv_alpha);
+                _mm_storeu_si128(ptr, _mm_adds_epu8(src, scaled));
+            }
+        }
+        len
+    }
+
+    pub fn scale_span(span: &mut [u8], factor: u8) -> usize {
+        let len = span.len() / 16 * 16;
+
+        // SAFETY: as above
+        unsafe {
+            let factor = _mm_set1_epi16(factor as i16);
+
+            for block in span[..len].chunks_exact_mut(16) {
+                let ptr = block.as_mut_ptr() as *mut __m128i;
+                _mm_storeu_si128(ptr, scale(_mm_loadu_si128(ptr), factor));
+            }
+        }
+        len
+    }
+
+    // `div255(byte * factor)` for each of 16 bytes, with 16 bit lanes
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    fn scale(bytes: __m128i, factor: __m128i) -> __m128i {
+        let zero = _mm_setzero_si128();
+        let low = div255(_mm_mullo_epi16(_mm_unpacklo_epi8(bytes, zero), factor));
+        let high = div255(_mm_mullo_epi16(_mm_unpackhi_epi8(bytes, zero), factor));
+        _mm_packus_epi16(low, high)
+    }
+
+    #[inline]
+    #[target_feature(enable = "sse2")]
+    fn div255(x: __m128i) -> __m128i {
+        let x = _mm_add_epi16(x, _mm_set1_epi16(128));
+        _mm_srli_epi16(_mm_add_epi16(x, _mm_srli_epi16(x, 8)), 8)
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod simd {
+    use std::arch::aarch64::*;
+
+    // NEON is part of aarch64, no need to check for it at runtime
+    pub fn over_span(span: &mut [u8], src: [u8; 4]) -> usize {
+        let len = span.len() / 16 * 16;
+
+        // SAFETY: NEON is always there on aarch64, and every load and store
+        // is within the first `len` bytes
+        unsafe {
+            let inv_alpha = vdup_n_u8(255 - src[3]);
+            let src = vreinterpretq_u8_u32(vdupq_n_u32(u32::from_ne_bytes(src)));
+
+            for block in span[..len].chunks_exact_mut(16) {
+                let ptr = block.as_mut_ptr();
+                let scaled = scale(vld1q_u8(ptr), inv_alpha);
+                vst1q_u8(ptr, vqaddq_u8(src, scaled));
+            }
+        }
+        len
+    }
+
+    pub fn scale_span(span: &mut [u8], factor: u8) -> usize {
+        let len = span.len() / 16 * 16;
+
+        // SAFETY: as above
+        unsafe {
+            let factor = vdup_n_u8(factor);
+
+            for block in span[..len].chunks_exact_mut(16) {
+                let ptr = block.as_mut_ptr();
+                vst1q_u8(ptr, scale(vld1q_u8(ptr), factor));
+            }
+        }
+        len
+    }
+
+    // `div255(byte * factor)` for each of 16 bytes, with 16 bit lanes
+    #[inline]
+    #[target_feature(enable = "neon")]
+    fn scale(bytes: uint8x16_t, factor: uint8x8_t) -> uint8x16_t {
+        let low = div255(vmull_u8(vget_low_u8(bytes), factor));
+        let high = div255(vmull_u8(vget_high_u8(bytes), factor));
+        vcombine_u8(low, high)
+    }
+
+    #[inline]
+    #[target_feature(enable = "neon")]
+    fn div255(x: uint16x8_t) -> uint8x8_t {
+        let x = vaddq_u16(x, vdupq_n_u16(128));
+        vshrn_n_u16(vaddq_u16(x, vshrq_n_u16(x, 8)), 8)
+    }
+}
+
+// Elsewhere it's all scalar
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod simd {
+    pub fn over_span(_: &mut [u8], _: [u8; 4]) -> usize {
+        0
+    }
+
+    pub fn scale_span(_: &mut [u8], _: u8) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Blending, TO_LINEAR, TO_SRGB, atop, blend, div255, over_span, premultiply, scalar,
+        scale_span,
+    };
+    use crate::theme::Bgra;
+
+    // Every byte value in every channel, plus a ragged tail
+    fn pixels() -> Vec<u8> {
+        (0..=255u8).cycle().take(256 * 4 * 3 + 7).collect()
+    }
+
+    #[test]
+    fn div255_rounds() {
+        for x in 0..=255 * 255 {
+            assert_eq!(div255(x) as u32, (x as f64 / 255.0).round() as u32, "{}", x);
+        }
+        assert_eq!(div255(255 * 255), 255);
+    }
+
+    #[test]
+    fn vectorized_matches_scalar() {
+        for alpha in [0, 1, 127, 128, 200, 254, 255] {
+            // Premultiplied, no channel above the alpha
+            let src = [alpha / 3, alpha / 2, alpha, alpha];
+
+            let mut fast = pixels();
+            let mut reference = pixels();
+            over_span(&mut fast, src);
+            scalar::over_span(&mut reference, src);
+            assert_eq!(fast, reference, "over {:?}", src);
+        }
+
+        for factor in 0..=255 {
+            let mut fast = pixels();
+            let mut reference = pixels();
+            scale_span(&mut fast, factor);
+            scalar::scale_span(&mut reference, factor);
+            assert_eq!(fast, reference, "scale {}", factor);
+        }
+    }
+
+    #[test]
+    fn opaque_over_anything() {
+        let mut span = pixels();
+        over_span(&mut span, [10, 20, 30, 255]);
+        assert!(span.chunks_exact(4).all(|pixel| pixel == [10, 20, 30, 255]));
+
+        let mut span = pixels();
+        let before = span.clone();
+        over_span(&mut span, [0, 0, 0, 0]);
+        assert_eq!(span, before);
+    }
+
+    #[test]
+    fn premultiplied_edges() {
+        let white = Bgra::from_rgba(255, 255, 255, 255);
+        let translucent = Bgra::from_rgba(200, 100, 50, 242);
+
+        for blending in [Blending::Srgb, Blending::Linear] {
+            // Half covered over nothing is half transparent, not a dark fringe
+            let mut pixel = [0; 4];
+            blend(&mut pixel, white, 128, blending);
+            assert_eq!(pixel, [128; 4], "{:?}", blending);
+
+            // Coverage adds up in the destination alpha
+            blend(&mut pixel, white, 128, blending);
+            assert_eq!(pixel[3], 192, "{:?}", blending);
+
+            // The color's own alpha counts, once
+            let mut pixel = [0; 4];
+            blend(&mut pixel, translucent, 255, blending);
+            assert_eq!(pixel, premultiply(translucent, 255), "{:?}", blending);
+            assert_eq!(pixel[3], 242);
+
+            // Nothing covered changes nothing
+            let mut pixel = [10, 20, 30, 40];
+            blend(&mut pixel, white, 0, blending);
+            assert_eq!(pixel, [10, 20, 30, 40], "{:?}", blending);
+
+            // No channel ever above the alpha
+            for coverage in (0..=255).step_by(15) {
+                for dst_alpha in (0..=255).step_by(15) {
+                    let mut pixel = [dst_alpha / 2, dst_alpha, dst_alpha / 3, dst_alpha];
+                    blend(&mut pixel, translucent, coverage, blending);
+                    assert!(pixel[..3].iter().all(|c| *c <= pixel[3]), "{:?}", pixel);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn linear_light() {
+        for c in 0..=255u8 {
+            assert_eq!(TO_SRGB[TO_LINEAR[c as usize] as usize], c);
+        }
+
+        // Half white over opaque black is brighter mixed in linear light
+        let white = Bgra::from_rgba(255, 255, 255, 255);
+        let mut srgb = [0, 0, 0, 255];
+        let mut linear = [0, 0, 0, 255];
+        blend(&mut srgb, white, 128, Blending::Srgb);
+        blend(&mut linear, white, 128, Blending::Linear);
+        assert_eq!(srgb, [128, 128, 128, 255]);
+        assert_eq!(linear, [188, 188, 188, 255]);
+    }
+
+    #[test]
+    fn flash_only_whats_drawn() {
+        let mut empty = [0; 4];
+        atop(&mut empty, [100, 100, 100, 128]);
+        assert_eq!(empty, [0; 4]);
+
+        let mut opaque = [0, 0, 0, 255];
+        atop(&mut opaque, [100, 100, 100, 128]);
+        assert_eq!(opaque, [100, 100, 100, 255]);
+    }
+}
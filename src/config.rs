@@ -0,0 +1,482 @@
+use anyhow::Context;
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{command::parse_duration, keys::Keys, pomodoro::Phase, theme::Bgra};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // IANA zones shown side by side by the `world` command
+    pub world: Vec<Tz>,
+    // Close the popup after this long without any input
+    #[serde(deserialize_with = "optional_duration")]
+    pub timeout: Option<Duration>,
+    pub surface: SurfaceConfig,
+    pub calendar: CalendarConfig,
+    pub fonts: FontsConfig,
+    pub animation: AnimationConfig,
+    pub pointer: PointerConfig,
+    // Keysym names with modifiers for each action, as in `close = ["Escape", "q"]`
+    pub keys: Keys,
+    pub timer: TimerConfig,
+    pub alarm: AlarmConfig,
+    pub pomodoro: PomodoroConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SurfaceConfig {
+    pub anchor: Anchor,
+    // Distance from the anchored edges, in pixels
+    pub margin: i32,
+    pub blending: Blending,
+    pub renderer: Renderer,
+    // Ask the compositor to blur what's behind the popup, where it can
+    pub blur: bool,
+}
+
+impl Default for SurfaceConfig {
+    fn default() -> Self {
+        Self {
+            anchor: Anchor::default(),
+            margin: 0,
+            blending: Blending::default(),
+            renderer: Renderer::default(),
+            blur: true,
+        }
+    }
+}
+
+// How antialiased edges and translucent colors mix with what's under them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Blending {
+    // Straight on the sRGB values, like most toolkits
+    #[default]
+    Srgb,
+    // In linear light, heavier and truer to the colors on thin lines and text
+    Linear,
+}
+
+// What draws the shapes, the text is always cosmic-text's
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Renderer {
+    #[default]
+    Software,
+    // Needs the `tiny-skia` feature, falling back to software without it
+    TinySkia,
+}
+
+// Where the popup sits on the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    #[default]
+    Center,
+    Top,
+    TopLeft,
+    TopRight,
+    Bottom,
+    BottomLeft,
+    BottomRight,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalendarConfig {
+    // In pixels, as are the border and the shadow
+    pub corner_radius: u32,
+    pub border_width: u32,
+    // As in `#1e1e2e` or `#1e1e2e80`, the theme's frame color when unset
+    #[serde(deserialize_with = "optional_color")]
+    pub border_color: Option<Bgra>,
+    // How far a drop shadow spreads under the calendar, 0 for none
+    pub shadow: u32,
+    pub today: TodayStyle,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        Self {
+            corner_radius: 0,
+            border_width: 2,
+            border_color: None,
+            shadow: 0,
+            today: TodayStyle::default(),
+        }
+    }
+}
+
+// How today's date stands out from the rest of the month, always in bold
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TodayStyle {
+    // Larger, in the highlight color
+    #[default]
+    Bold,
+    // On a highlight disc
+    Circle,
+    // Inside a highlight ring
+    Ring,
+    // Over a highlight bar
+    Underline,
+    // The whole cell filled with the primary color
+    Inverted,
+}
+
+// The font for each kind of text
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FontsConfig {
+    pub month: FontConfig,
+    pub weekday: FontConfig,
+    pub day: FontConfig,
+    // The timer and the stopwatch
+    pub digits: FontConfig,
+    // Everything else, as the city under the clock
+    pub label: FontConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FontConfig {
+    // Looked up among the system fonts, the bundled Inter when unset or
+    // not installed
+    pub family: Option<String>,
+    // From 100 for thin to 900 for black, 400 being regular, and anything
+    // from 1 to 1000 in between
+    #[serde(deserialize_with = "weight")]
+    pub weight: u16,
+    pub style: FontStyle,
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            family: None,
+            weight: 400,
+            style: FontStyle::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnimationConfig {
+    pub style: AnimationStyle,
+    // How long showing or hiding the popup takes, 0 to skip it
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationStyle {
+    None,
+    #[default]
+    Fade,
+    // Grows from slightly smaller while fading in
+    Scale,
+    // Comes in from the anchored edge, or from below when centered
+    Slide,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        Self {
+            style: AnimationStyle::default(),
+            duration_ms: 150,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PointerConfig {
+    pub close_on_click: CloseOnClick,
+    // Held while dragging an anchored popup around
+    pub drag_modifier: DragModifier,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloseOnClick {
+    #[default]
+    Never,
+    // Close when keyboard focus is lost, which a click elsewhere does, but
+    // so do notifications, workspace switches and other popups
+    Outside,
+    Inside,
+    // Both of the above
+    Anywhere,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DragModifier {
+    #[default]
+    Alt,
+    Ctrl,
+    Shift,
+    Super,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimerConfig {
+    pub style: TimerStyle,
+    // Run through `sh -c` when a timer expires, with its name in `$SOMETIME_TIMER`
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimerStyle {
+    // An arc filling the clock face as time goes by
+    #[default]
+    Arc,
+    Digits,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlarmConfig {
+    // Run through `sh -c` when an alarm rings, with its label in `$SOMETIME_ALARM`
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PomodoroConfig {
+    #[serde(deserialize_with = "duration")]
+    pub work: Duration,
+    #[serde(deserialize_with = "duration")]
+    pub short_break: Duration,
+    #[serde(deserialize_with = "duration")]
+    pub long_break: Duration,
+    // Work sessions before a long break
+    pub cycles: u32,
+    // Run through `sh -c` when a phase ends, with the next one in `$SOMETIME_POMODORO`
+    pub command: Option<String>,
+}
+
+impl PomodoroConfig {
+    pub fn duration(&self, phase: Phase) -> Duration {
+        match phase {
+            Phase::Work => self.work,
+            Phase::ShortBreak => self.short_break,
+            Phase::LongBreak => self.long_break,
+        }
+    }
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            work: Duration::from_secs(25 * 60),
+            short_break: Duration::from_secs(5 * 60),
+            long_break: Duration::from_secs(15 * 60),
+            cycles: 4,
+            command: None,
+        }
+    }
+}
+
+// Durations are written like on the command line, as in `25m`
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_duration(&s).map_err(serde::de::Error::custom)
+}
+
+fn optional_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    duration(deserializer).map(Some)
+}
+
+fn weight<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u16, D::Error> {
+    match u16::deserialize(deserializer)? {
+        weight @ 1..=1000 => Ok(weight),
+        weight => Err(serde::de::Error::custom(format!(
+            "Font weight {} isn't between 1 and 1000",
+            weight
+        ))),
+    }
+}
+
+fn optional_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Bgra>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            world: vec![Tz::UTC],
+            timeout: None,
+            surface: SurfaceConfig::default(),
+            calendar: CalendarConfig::default(),
+            fonts: FontsConfig::default(),
+            animation: AnimationConfig::default(),
+            pointer: PointerConfig::default(),
+            keys: Keys::default(),
+            timer: TimerConfig::default(),
+            alarm: AlarmConfig::default(),
+            pomodoro: PomodoroConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    // Reads `$XDG_CONFIG_HOME/sometime/config.toml`, falling back to the
+    // defaults when the file doesn't exist
+    pub fn load() -> anyhow::Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                toml::from_str(&contents).with_context(|| format!("invalid {}", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("can't read {}", path.display())),
+        }
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_home.join("sometime").join("config.toml"))
+    }
+}
+
+// A file under `$XDG_STATE_HOME/sometime`, for what outlives the daemon
+pub(crate) fn state_path(name: &str) -> Option<PathBuf> {
+    let state_home = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        })?;
+
+    Some(state_home.join("sometime").join(name))
+}
+
+// Replace a state file without leaving it half written
+pub(crate) fn write_state(path: &Path, contents: &str) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("can't create {}", dir.display()))?;
+    }
+
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, contents).with_context(|| format!("can't write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("can't write {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Anchor, AnimationStyle, Blending, CloseOnClick, Config, DragModifier, FontStyle, Renderer,
+        TimerStyle, TodayStyle,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn parse_config() {
+        let config: Config = toml::from_str(
+            r##"
+            world = ["Asia/Tokyo", "Europe/Lisbon"]
+            timeout = "10s"
+
+            [surface]
+            anchor = "top-right"
+            margin = 8
+            blending = "linear"
+            renderer = "tiny-skia"
+            blur = false
+
+            [calendar]
+            corner_radius = 12
+            border_color = "#f5c2e780"
+            shadow = 8
+            today = "ring"
+
+            [fonts.month]
+            family = "DejaVu Serif"
+            weight = 700
+
+            [fonts.digits]
+            style = "italic"
+
+            [animation]
+            style = "slide"
+
+            [pointer]
+            close_on_click = "anywhere"
+
+            [timer]
+            style = "digits"
+
+            [pomodoro]
+            work = "50m"
+            cycles = 2
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(config.world.len(), 2);
+        assert_eq!(config.timeout, Some(Duration::from_secs(10)));
+        assert_eq!(config.surface.anchor, Anchor::TopRight);
+        assert_eq!(config.surface.blending, Blending::Linear);
+        assert_eq!(config.surface.renderer, Renderer::TinySkia);
+        assert!(!config.surface.blur);
+        assert_eq!(config.calendar.corner_radius, 12);
+        assert_eq!(config.calendar.border_width, 2);
+        assert_eq!(
+            config
+                .calendar
+                .border_color
+                .map(|c| [c.r(), c.g(), c.b(), c.a()]),
+            Some([245, 194, 231, 128])
+        );
+        assert_eq!(config.calendar.shadow, 8);
+        assert_eq!(config.calendar.today, TodayStyle::Ring);
+        assert_eq!(config.fonts.month.family.as_deref(), Some("DejaVu Serif"));
+        assert_eq!(config.fonts.month.weight, 700);
+        assert_eq!(config.fonts.digits.style, FontStyle::Italic);
+        assert_eq!(config.fonts.day.weight, 400);
+        assert_eq!(config.animation.style, AnimationStyle::Slide);
+        assert_eq!(config.animation.duration_ms, 150);
+        assert_eq!(config.pointer.close_on_click, CloseOnClick::Anywhere);
+        assert_eq!(config.pointer.drag_modifier, DragModifier::Alt);
+        assert_eq!(config.timer.style, TimerStyle::Digits);
+        assert_eq!(config.pomodoro.work, Duration::from_secs(50 * 60));
+        assert_eq!(config.pomodoro.short_break, Duration::from_secs(5 * 60));
+        assert_eq!(config.pomodoro.cycles, 2);
+
+        assert!(toml::from_str::<Config>("world = [\"Mars/Olympus\"]").is_err());
+        assert!(toml::from_str::<Config>("[pomodoro]\nwork = \"soon\"").is_err());
+        assert!(toml::from_str::<Config>("colour = \"pink\"").is_err());
+        assert!(toml::from_str::<Config>("[fonts.day]\nweight = 0").is_err());
+        assert!(toml::from_str::<Config>("[fonts.day]\nweight = 1001").is_err());
+    }
+}
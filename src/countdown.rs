@@ -0,0 +1,153 @@
+use smithay_client_toolkit::reexports::calloop::{
+    RegistrationToken,
+    timer::{TimeoutAction, Timer},
+};
+use std::time::{Duration, Instant};
+use wayland_client::QueueHandle;
+
+use crate::{
+    Event, Sometime, State, View,
+    command::format_duration,
+    hook,
+    ipc::{Notice, Payload, Reply, TimerState},
+};
+
+pub struct Countdown {
+    pub name: String,
+    pub duration: Duration,
+    pub deadline: Instant,
+    token: RegistrationToken,
+}
+
+impl Countdown {
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+
+    // How much of the countdown already went by, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        1.0 - self.remaining().as_secs_f32() / self.duration.as_secs_f32()
+    }
+
+    pub fn timer_state(&self) -> TimerState {
+        TimerState {
+            name: self.name.clone(),
+            duration_secs: self.duration.as_secs(),
+            remaining_ms: self.remaining().as_millis() as u64,
+        }
+    }
+}
+
+impl Sometime {
+    pub(crate) fn start_countdown(
+        &mut self,
+        duration: Duration,
+        name: Option<String>,
+        qh: &QueueHandle<Self>,
+    ) -> anyhow::Result<Reply> {
+        let name = name.unwrap_or_else(|| self.unused_countdown_name());
+
+        if self
+            .countdowns
+            .iter()
+            .any(|countdown| countdown.name == name)
+        {
+            anyhow::bail!("there's already a timer named {}", name);
+        }
+
+        let Some(deadline) = Instant::now().checked_add(duration) else {
+            anyhow::bail!("a timer for {} is too long", format_duration(duration));
+        };
+        let qh = qh.clone();
+        let expired = name.clone();
+        let token = self
+            .loop_handle
+            .insert_source(Timer::from_deadline(deadline), move |_, _, app| {
+                app.countdown_expired(&expired, &qh);
+                TimeoutAction::Drop
+            })
+            .map_err(|e| e.error)?;
+
+        let countdown = Countdown {
+            name,
+            duration,
+            deadline,
+            token,
+        };
+        let reply = Reply::State(Payload::Timers {
+            timers: vec![countdown.timer_state()],
+        });
+
+        self.countdowns.push(countdown);
+        self.countdowns.sort_by_key(|countdown| countdown.deadline);
+
+        if self.state == State::Awake(View::Timer) {
+            self.draw();
+        }
+
+        Ok(reply)
+    }
+
+    pub(crate) fn cancel_countdown(&mut self, name: &str) -> anyhow::Result<Reply> {
+        let Some(idx) = self.countdowns.iter().position(|c| c.name == name) else {
+            anyhow::bail!("no timer named {}", name);
+        };
+
+        let countdown = self.countdowns.remove(idx);
+        self.loop_handle.remove(countdown.token);
+
+        if self.state == State::Awake(View::Timer) {
+            self.draw();
+        }
+
+        Ok(Reply::Ok)
+    }
+
+    pub(crate) fn list_countdowns(&self) -> Payload {
+        Payload::Timers {
+            timers: self.countdowns.iter().map(Countdown::timer_state).collect(),
+        }
+    }
+
+    fn countdown_expired(&mut self, name: &str, qh: &QueueHandle<Self>) {
+        self.countdowns.retain(|countdown| countdown.name != name);
+        self.last_expired = Some(name.into());
+        self.notify(Notice::TimerExpired { name: name.into() });
+
+        if let Some(command) = &self.config.timer.command {
+            hook::run(command, &[("SOMETIME_TIMER", name)]);
+        }
+
+        // Onto the timer view whatever's open, so the expiry is seen
+        let before = self.state;
+        self.handle(Event::Show(View::Timer), qh);
+        if self.state == before {
+            self.draw();
+        }
+
+        self.flash();
+    }
+
+    // `timer`, `timer-2`, `timer-3`...
+    fn unused_countdown_name(&self) -> String {
+        (1..)
+            .map(|n| match n {
+                1 => "timer".to_string(),
+                n => format!("timer-{}", n),
+            })
+            .find(|name| self.countdowns.iter().all(|c| &c.name != name))
+            .unwrap()
+    }
+}
+
+// `4:05` or `1:04:05`, rounding up so it only shows `0:00` once it's over
+pub fn format_remaining(remaining: Duration) -> String {
+    let secs = remaining.as_millis().div_ceil(1000);
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
@@ -0,0 +1,165 @@
+// A region of the surface, in buffer pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+// Past this many regions, damaging them one by one isn't worth it
+const MAX_RECTS: usize = 16;
+
+impl Rect {
+    pub fn full(side: i32) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: side,
+            height: side,
+        }
+    }
+
+    // The smallest rectangle around two corners, inclusive
+    pub fn around(x0: i32, y0: i32, x1: i32, y1: i32) -> Self {
+        Self {
+            x: x0.min(x1),
+            y: y0.min(y1),
+            width: (x1 - x0).abs() + 1,
+            height: (y1 - y0).abs() + 1,
+        }
+    }
+
+    // The part of it on a `side` by `side` surface
+    pub fn clip(self, side: i32) -> Option<Self> {
+        let (x0, y0) = (self.x.max(0), self.y.max(0));
+        let (x1, y1) = (
+            (self.x + self.width).min(side),
+            (self.y + self.height).min(side),
+        );
+
+        (x0 < x1 && y0 < y1).then_some(Self {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        })
+    }
+
+    fn contains(&self, other: &Self) -> bool {
+        self.x <= other.x
+            && self.y <= other.y
+            && self.x + self.width >= other.x + other.width
+            && self.y + self.height >= other.y + other.height
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let (x0, y0) = (self.x.min(other.x), self.y.min(other.y));
+        let x1 = (self.x + self.width).max(other.x + other.width);
+        let y1 = (self.y + self.height).max(other.y + other.height);
+
+        Self {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        }
+    }
+
+    // Copy this region of a `side` by `side` BGRA frame
+    pub fn copy(&self, side: i32, from: &[u8], to: &mut [u8]) {
+        for y in self.y..self.y + self.height {
+            let start = ((y * side + self.x) * 4) as usize;
+            let end = start + (self.width * 4) as usize;
+            to[start..end].copy_from_slice(&from[start..end]);
+        }
+    }
+
+    // The pixels mostly inside it with its corners rounded by `radius`, as
+    // bands of rows alike
+    pub fn rows(&self, radius: f32) -> Vec<Rect> {
+        let radius = radius.clamp(0.0, self.width.min(self.height) as f32 / 2.0);
+        let (top, bottom) = (
+            self.y as f32 + radius,
+            (self.y + self.height) as f32 - radius,
+        );
+        let mut rows: Vec<Rect> = Vec::new();
+
+        for y in self.y..self.y + self.height {
+            // How far into a corner the middle of the row is
+            let center = y as f32 + 0.5;
+            let dy = (top - center).max(center - bottom).max(0.0);
+            let inset = (radius - (radius * radius - dy * dy).max(0.0).sqrt()).round() as i32;
+
+            let row = Rect {
+                x: self.x + inset,
+                y,
+                width: self.width - 2 * inset,
+                height: 1,
+            };
+            match rows.last_mut() {
+                Some(last) if last.x == row.x && last.width == row.width => last.height += 1,
+                _ if row.width > 0 => rows.push(row),
+                _ => {}
+            }
+        }
+        rows
+    }
+}
+
+// Add a region to a list of them, skipping what's already covered and
+// collapsing the list into one once it grows too long
+pub fn add(rects: &mut Vec<Rect>, rect: Rect) {
+    if rects.iter().any(|r| r.contains(&rect)) {
+        return;
+    }
+    rects.retain(|r| !rect.contains(r));
+
+    if rects.len() < MAX_RECTS {
+        rects.push(rect);
+    } else {
+        let bounds = rects.iter().fold(rect, |bounds, r| bounds.union(r));
+        rects.clear();
+        rects.push(bounds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_RECTS, Rect, add};
+
+    #[test]
+    fn clip_to_surface() {
+        let rect = Rect::around(-4, 10, 20, 30).clip(16).unwrap();
+        assert_eq!(rect, Rect::around(0, 10, 15, 15));
+        assert_eq!(Rect::around(20, 20, 30, 30).clip(16), None);
+    }
+
+    #[test]
+    fn add_regions() {
+        let mut rects = Vec::new();
+        add(&mut rects, Rect::around(10, 10, 20, 20));
+        add(&mut rects, Rect::around(12, 12, 18, 18));
+        assert_eq!(rects, [Rect::around(10, 10, 20, 20)]);
+
+        add(&mut rects, Rect::around(0, 0, 30, 30));
+        assert_eq!(rects, [Rect::around(0, 0, 30, 30)]);
+
+        for i in 1..=MAX_RECTS as i32 {
+            add(&mut rects, Rect::around(40 * i, 0, 40 * i + 1, 1));
+        }
+        assert_eq!(rects, [Rect::around(0, 0, 40 * MAX_RECTS as i32 + 1, 30)]);
+    }
+
+    #[test]
+    fn rounded_rows() {
+        let rect = Rect::around(0, 0, 19, 9);
+        assert_eq!(rect.rows(0.0), [rect]);
+
+        let rows = rect.rows(4.0);
+        assert_eq!(rows.first(), Some(&Rect::around(2, 0, 17, 0)));
+        assert_eq!(rows.iter().map(|r| r.height).sum::<i32>(), 10);
+        assert!(rows.iter().any(|r| r.width == 20));
+        assert_eq!(rows.first().map(|r| r.x), rows.last().map(|r| r.x));
+    }
+}
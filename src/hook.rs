@@ -0,0 +1,17 @@
+// Runs a configured shell command in the background, the daemon never waits
+// for it
+pub fn run(command: &str, envs: &[(&str, &str)]) {
+    let child = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(envs.iter().copied())
+        .spawn();
+
+    match child {
+        // Reap it once it exits
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => eprintln!("Failed to run `{}`: {}", command, e),
+    }
+}
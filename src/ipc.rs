@@ -1,11 +1,245 @@
 use anyhow::Context;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use std::{
-    io::{BufRead, BufReader, Read, Write},
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
     os::unix::net::{UnixListener, UnixStream},
     path::PathBuf,
+    time::Duration,
 };
 
-use crate::Command;
+use crate::{
+    Alarm, Command, Invocation, Phase, State, command::format_duration,
+    countdown::format_remaining, stopwatch::format_elapsed, theme::ThemeName,
+};
+
+// Bumped whenever a request or reply changes shape
+pub const VERSION: u32 = 1;
+
+// One line of JSON from a client, such as
+// `{"version":1,"command":"timer","args":["25m","--name","tea"]}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Request {
+    pub version: u32,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+// One line of JSON back from the daemon, such as `{"version":1,"reply":"ok"}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub version: u32,
+    #[serde(flatten)]
+    pub reply: Reply,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "reply", rename_all = "snake_case")]
+pub enum Reply {
+    Ok,
+    Error { message: String },
+    State(Payload),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Payload {
+    Timers {
+        timers: Vec<TimerState>,
+    },
+    Alarms {
+        alarms: Vec<Alarm>,
+    },
+    Stopwatch {
+        running: bool,
+        elapsed_ms: u64,
+        // Most recent first
+        laps_ms: Vec<u64>,
+    },
+    Pomodoro {
+        phase: Phase,
+        running: bool,
+        remaining_secs: u64,
+        completed: u32,
+    },
+    Status {
+        #[serde(flatten)]
+        state: State,
+        // Name of the output the surface is on
+        output: Option<String>,
+        uptime_secs: u64,
+        theme: ThemeName,
+        config: Option<PathBuf>,
+        timers: Vec<TimerState>,
+        alarms: Vec<Alarm>,
+    },
+}
+
+// One line of JSON streamed to `subscribe` connections after their reply,
+// such as `{"event":"state","state":"awake","view":"clock"}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notice {
+    State {
+        #[serde(flatten)]
+        state: State,
+    },
+    TimerExpired {
+        name: String,
+    },
+    DaySelected {
+        date: NaiveDate,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimerState {
+    pub name: String,
+    pub duration_secs: u64,
+    pub remaining_ms: u64,
+}
+
+impl Request {
+    pub fn new(invocation: &Invocation) -> Self {
+        let mut args = invocation.args();
+        let command = args.remove(0);
+
+        Self {
+            version: VERSION,
+            command,
+            args,
+        }
+    }
+
+    pub fn invocation(&self) -> Result<Invocation, String> {
+        if self.version != VERSION {
+            return Err(format!("Unsupported protocol version: {}", self.version));
+        }
+
+        let args = std::iter::once(&self.command).chain(&self.args);
+        Invocation::from_args(args.map(String::as_str))
+    }
+}
+
+impl Reply {
+    pub fn error(message: impl std::fmt::Display) -> Self {
+        Self::Error {
+            message: message.to_string(),
+        }
+    }
+}
+
+// How the CLI and the word protocol print replies
+impl std::fmt::Display for Reply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok => write!(f, "is happening"),
+            Self::Error { message } => write!(f, "Error: {}", message),
+            Self::State(payload) => write!(f, "{}", payload),
+        }
+    }
+}
+
+impl std::fmt::Display for Payload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timers { timers } if timers.is_empty() => write!(f, "no timers"),
+            Self::Timers { timers } => {
+                let lines: Vec<String> = timers
+                    .iter()
+                    .map(|timer| {
+                        format!(
+                            "{} {} of {}",
+                            timer.name,
+                            format_remaining(Duration::from_millis(timer.remaining_ms)),
+                            format_duration(Duration::from_secs(timer.duration_secs))
+                        )
+                    })
+                    .collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Self::Alarms { alarms } if alarms.is_empty() => write!(f, "no alarms"),
+            Self::Alarms { alarms } => {
+                let lines: Vec<String> = alarms.iter().map(Alarm::to_string).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Self::Stopwatch {
+                elapsed_ms,
+                laps_ms,
+                ..
+            } => {
+                write!(f, "{}", format_elapsed(Duration::from_millis(*elapsed_ms)))?;
+                for (i, lap_ms) in laps_ms.iter().enumerate() {
+                    let n = laps_ms.len() - i;
+                    let lap = format_elapsed(Duration::from_millis(*lap_ms));
+                    write!(f, "\nLap {}  {}", n, lap)?;
+                }
+                Ok(())
+            }
+            Self::Pomodoro {
+                phase,
+                running,
+                remaining_secs,
+                completed,
+            } => {
+                let remaining = format_remaining(Duration::from_secs(*remaining_secs));
+                let paused = if *running { "" } else { " (paused)" };
+                write!(
+                    f,
+                    "{} {} left{}, {} done",
+                    phase, remaining, paused, completed
+                )
+            }
+            Self::Status {
+                state,
+                output,
+                uptime_secs,
+                theme,
+                config,
+                timers,
+                alarms,
+            } => {
+                write!(f, "{}", state)?;
+                if let Some(output) = output {
+                    write!(f, " on {}", output)?;
+                }
+                let uptime = match uptime_secs {
+                    0 => "0s".into(),
+                    secs => format_duration(Duration::from_secs(*secs)),
+                };
+                write!(f, "\nup {}", uptime)?;
+                write!(f, "\ntheme {}", theme)?;
+                if let Some(config) = config {
+                    write!(f, "\nconfig {}", config.display())?;
+                }
+                if !timers.is_empty() {
+                    let timers = Self::Timers {
+                        timers: timers.clone(),
+                    };
+                    write!(f, "\n{}", timers)?;
+                }
+                if !alarms.is_empty() {
+                    let alarms = Self::Alarms {
+                        alarms: alarms.clone(),
+                    };
+                    write!(f, "\n{}", alarms)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Notice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::State { state } => write!(f, "{}", state),
+            Self::TimerExpired { name } => write!(f, "{} expired", name),
+            Self::DaySelected { date } => write!(f, "{} selected", date),
+        }
+    }
+}
 
 // === daemon ===
 
@@ -19,22 +253,91 @@ pub fn setup_listener() -> anyhow::Result<UnixListener> {
     Ok(UnixListener::bind(&socket_path)?)
 }
 
-pub fn recv_cmd(stream: UnixStream) -> anyhow::Result<Command> {
-    let mut reader = BufReader::new(stream);
-    let mut line = String::new();
+// A connected client, waiting for its reply
+pub struct Client {
+    stream: UnixStream,
+    // Sent a bare word command rather than JSON
+    words: bool,
+}
 
-    reader.read_line(&mut line)?;
+// Longest request a client may send
+const MAX_REQUEST: usize = 64 * 1024;
 
-    let line = line.trim();
+// Read what's arrived of a request without waiting for more; true once the
+// line is complete or the client stopped sending
+pub fn read_request(mut stream: &UnixStream, line: &mut Vec<u8>) -> io::Result<bool> {
+    let mut buf = [0; 1024];
 
-    match line.parse() {
-        Ok(command) => {
-            let mut stream = reader.into_inner();
-            stream.write_all(b"is happening")?;
-            stream.flush()?;
-            Ok(command)
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) => return Ok(true),
+            Ok(n) => {
+                line.extend_from_slice(&buf[..n]);
+                if buf[..n].contains(&b'\n') {
+                    return Ok(true);
+                }
+                if line.len() > MAX_REQUEST {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "request too long"));
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
         }
-        Err(e) => Err(anyhow::anyhow!("{}", e)),
+    }
+}
+
+// Parse a request read by `read_request`, keeping the connection to reply
+// on even when it doesn't parse
+pub fn recv_cmd(
+    stream: UnixStream,
+    line: &[u8],
+) -> anyhow::Result<(Result<Invocation, String>, Client)> {
+    // Don't let a client that won't read its reply stall the daemon
+    stream.set_nonblocking(false)?;
+    stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+
+    let line = String::from_utf8_lossy(line);
+    let line = line.lines().next().unwrap_or_default().trim();
+    let words = !line.starts_with('{');
+
+    let invocation = if words {
+        Invocation::from_args(line.split_whitespace())
+    } else {
+        serde_json::from_str::<Request>(line)
+            .map_err(|e| format!("Invalid request: {}", e))
+            .and_then(|request| request.invocation())
+    };
+
+    Ok((invocation, Client { stream, words }))
+}
+
+impl Client {
+    pub fn reply(mut self, reply: Reply) -> anyhow::Result<()> {
+        // Every reply is a line, so notices after it start on their own
+        if self.words {
+            writeln!(self.stream, "{}", reply)?;
+        } else {
+            let response = Response {
+                version: VERSION,
+                reply,
+            };
+            serde_json::to_writer(&mut self.stream, &response)?;
+            self.stream.write_all(b"\n")?;
+        }
+
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    // Acknowledge a `subscribe` and hand over the connection for notices
+    pub fn subscribe(self) -> anyhow::Result<UnixStream> {
+        let stream = self.stream.try_clone()?;
+        self.reply(Reply::Ok)?;
+
+        // A stalled subscriber is dropped rather than stalling the daemon
+        stream.set_nonblocking(true)?;
+        Ok(stream)
     }
 }
 
@@ -46,15 +349,41 @@ pub fn unlink_socket() -> anyhow::Result<()> {
 
 // === cli ===
 
-pub fn invoke_daemon(command: Command) -> anyhow::Result<String> {
+pub fn invoke_daemon(invocation: &Invocation) -> anyhow::Result<Reply> {
+    let (reply, _) = request(invocation)?;
+    Ok(reply)
+}
+
+// Hand every notice line to `on_notice` until the daemon goes away
+pub fn subscribe(mut on_notice: impl FnMut(&str, Notice)) -> anyhow::Result<()> {
+    let (reply, reader) = request(&Command::Subscribe.into())?;
+
+    if let Reply::Error { message } = reply {
+        anyhow::bail!(message);
+    }
+
+    for line in reader.lines() {
+        let line = line?;
+        let notice = serde_json::from_str(&line).context("invalid notice")?;
+        on_notice(&line, notice);
+    }
+
+    Ok(())
+}
+
+fn request(invocation: &Invocation) -> anyhow::Result<(Reply, BufReader<UnixStream>)> {
     let socket_path = socket_path()?;
     let mut stream = UnixStream::connect(&socket_path).context("is sometime-daemon running?")?;
-    stream.write_all(command.to_string().as_bytes())?;
+
+    serde_json::to_writer(&mut stream, &Request::new(invocation))?;
     stream.write_all(b"\n")?;
 
-    let mut response = String::new();
-    stream.read_to_string(&mut response)?;
-    Ok(response.trim().to_string())
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    let response: Response = serde_json::from_str(&line).context("invalid reply")?;
+    Ok((response.reply, reader))
 }
 
 // === shared ===
@@ -63,3 +392,151 @@ fn socket_path() -> anyhow::Result<PathBuf> {
     let xdg_runtime = std::env::var("XDG_RUNTIME_DIR")?;
     Ok(PathBuf::from(xdg_runtime).join("sometime.sock"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Notice, Payload, Reply, Request, Response, VERSION, read_request, recv_cmd};
+    use crate::{Command, Invocation, Phase, State, View, theme::ThemeName};
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+    };
+
+    #[test]
+    fn request_carries_arguments_with_spaces() {
+        let line =
+            r#"{"version":1,"command":"alarm","args":["add","07:30","--label","team standup"]}"#;
+        let request: Request = serde_json::from_str(line).unwrap();
+
+        let Ok(Command::AddAlarm { label, .. }) = request.invocation().map(|i| i.command) else {
+            panic!("not an alarm");
+        };
+        assert_eq!(label.as_deref(), Some("team standup"));
+    }
+
+    #[test]
+    fn request_round_trips() {
+        let invocation = Invocation {
+            command: Command::Clock(Some(chrono_tz::Asia::Tokyo)),
+            timeout: Some(std::time::Duration::from_secs(30)),
+            peek: true,
+        };
+        let request = Request::new(&invocation);
+
+        assert_eq!(request.command, "clock");
+        assert_eq!(request.invocation(), Ok(invocation));
+    }
+
+    #[test]
+    fn reject_other_versions() {
+        let request: Request = serde_json::from_str(r#"{"version":2,"command":"clock"}"#).unwrap();
+        assert!(request.invocation().is_err());
+    }
+
+    #[test]
+    fn typed_replies() {
+        let ok = Response {
+            version: VERSION,
+            reply: Reply::Ok,
+        };
+        assert_eq!(
+            serde_json::to_string(&ok).unwrap(),
+            r#"{"version":1,"reply":"ok"}"#
+        );
+
+        let error = Response {
+            version: VERSION,
+            reply: Reply::error("no timer named tea"),
+        };
+        assert_eq!(
+            serde_json::to_string(&error).unwrap(),
+            r#"{"version":1,"reply":"error","message":"no timer named tea"}"#
+        );
+
+        let state = Response {
+            version: VERSION,
+            reply: Reply::State(Payload::Pomodoro {
+                phase: Phase::ShortBreak,
+                running: true,
+                remaining_secs: 300,
+                completed: 1,
+            }),
+        };
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(
+            json,
+            r#"{"version":1,"reply":"state","kind":"pomodoro","phase":"short-break","running":true,"remaining_secs":300,"completed":1}"#
+        );
+        assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), state);
+    }
+
+    #[test]
+    fn notices() {
+        let opened = Notice::State {
+            state: State::WakeUp(View::Clock),
+        };
+        assert_eq!(
+            serde_json::to_string(&opened).unwrap(),
+            r#"{"event":"state","state":"wake_up","view":"clock"}"#
+        );
+
+        let expired = r#"{"event":"timer_expired","name":"tea"}"#;
+        assert_eq!(
+            serde_json::from_str::<Notice>(expired).unwrap(),
+            Notice::TimerExpired { name: "tea".into() }
+        );
+    }
+
+    #[test]
+    fn status_flattens_the_state() {
+        let status = Payload::Status {
+            state: State::Awake(View::Calendar),
+            output: Some("DP-1".into()),
+            uptime_secs: 90,
+            theme: ThemeName::Mocha,
+            config: None,
+            timers: vec![],
+            alarms: vec![],
+        };
+        let json = serde_json::to_string(&status).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"status","state":"awake","view":"calendar","output":"DP-1","uptime_secs":90,"theme":"mocha","config":null,"timers":[],"alarms":[]}"#
+        );
+        assert_eq!(serde_json::from_str::<Payload>(&json).unwrap(), status);
+
+        assert_eq!(
+            serde_json::to_string(&State::Sleep).unwrap(),
+            r#"{"state":"sleep"}"#
+        );
+    }
+
+    #[test]
+    fn replies_end_lines() {
+        for request in ["subscribe\n", "{\"version\":1,\"command\":\"subscribe\"}\n"] {
+            let (mut cli, daemon) = UnixStream::pair().unwrap();
+            daemon.set_nonblocking(true).unwrap();
+            let mut line = Vec::new();
+
+            // Nothing yet, then half the request, then the rest
+            assert!(!read_request(&daemon, &mut line).unwrap());
+            let (start, rest) = request.split_at(4);
+            cli.write_all(start.as_bytes()).unwrap();
+            assert!(!read_request(&daemon, &mut line).unwrap());
+            cli.write_all(rest.as_bytes()).unwrap();
+            assert!(read_request(&daemon, &mut line).unwrap());
+
+            let (invocation, client) = recv_cmd(daemon, &line).unwrap();
+            assert_eq!(invocation.map(|i| i.command), Ok(Command::Subscribe));
+            let mut stream = client.subscribe().unwrap();
+            stream
+                .write_all(b"{\"event\":\"timer_expired\",\"name\":\"tea\"}\n")
+                .unwrap();
+
+            let mut lines = BufReader::new(cli).lines();
+            lines.next().unwrap().unwrap();
+            let notice = lines.next().unwrap().unwrap();
+            assert!(serde_json::from_str::<Notice>(&notice).is_ok(), "{notice}");
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use serde::{Deserialize, Deserializer};
+use smithay_client_toolkit::seat::keyboard::{Keysym, Modifiers};
+use std::collections::HashMap;
+use xkbcommon::xkb;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyAction {
+    Close,
+    Clock,
+    Calendar,
+    World,
+    Timer,
+    Stopwatch,
+    NextMonth,
+    PreviousMonth,
+    StopwatchToggle,
+    StopwatchLap,
+    StopwatchReset,
+    // The timer ending soonest, in the timer view
+    TimerCancel,
+    // In the clock view, where the pomodoro ring is
+    PomodoroToggle,
+    PomodoroSkip,
+}
+
+// A key with the modifiers held along with it, as in `Ctrl+Shift+n`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    keysym: Keysym,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+    logo: bool,
+}
+
+// Every binding and what it does, the defaults unless the config replaces
+// them action by action
+#[derive(Debug, Clone)]
+pub struct Keys(Vec<(Binding, KeyAction)>);
+
+const DEFAULT_KEYS: &[(KeyAction, &[&str])] = &[
+    (KeyAction::Close, &["Escape", "q"]),
+    (KeyAction::Clock, &["1"]),
+    (KeyAction::Calendar, &["2"]),
+    (KeyAction::World, &["3"]),
+    (KeyAction::Timer, &["4"]),
+    (KeyAction::Stopwatch, &["5"]),
+    (KeyAction::NextMonth, &["Right", "Page_Down"]),
+    (KeyAction::PreviousMonth, &["Left", "Page_Up"]),
+    (KeyAction::StopwatchToggle, &["space"]),
+    (KeyAction::StopwatchLap, &["l"]),
+    (KeyAction::StopwatchReset, &["r"]),
+    (KeyAction::TimerCancel, &["Delete"]),
+    (KeyAction::PomodoroToggle, &["p"]),
+    (KeyAction::PomodoroSkip, &["s"]),
+];
+
+impl Binding {
+    pub fn pressed(keysym: Keysym, modifiers: Modifiers) -> Self {
+        Self {
+            keysym: lowercase(keysym),
+            ctrl: modifiers.ctrl,
+            alt: modifiers.alt,
+            shift: modifiers.shift,
+            logo: modifiers.logo,
+        }
+    }
+
+    fn matches(&self, pressed: &Self) -> bool {
+        // Shift is part of symbols like `question`, only letters need it spelled out
+        let shift = self.shift == pressed.shift || (!self.shift && self.is_symbol());
+
+        self.keysym == pressed.keysym
+            && self.ctrl == pressed.ctrl
+            && self.alt == pressed.alt
+            && self.logo == pressed.logo
+            && shift
+    }
+
+    // Whether some key press matches both
+    fn overlaps(&self, other: &Self) -> bool {
+        let shift = self.shift == other.shift || self.is_symbol();
+
+        self.keysym == other.keysym
+            && self.ctrl == other.ctrl
+            && self.alt == other.alt
+            && self.logo == other.logo
+            && shift
+    }
+
+    // No case to it, so Shift doesn't change it
+    fn is_symbol(&self) -> bool {
+        lowercase(self.keysym) == uppercase(self.keysym)
+    }
+}
+
+impl std::str::FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifiers, key) = s.rsplit_once('+').unwrap_or(("", s));
+        let mut binding = Self {
+            keysym: keysym_from_name(key).ok_or_else(|| format!("Unknown keysym: {}", key))?,
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+        };
+
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => binding.ctrl = true,
+                "alt" => binding.alt = true,
+                "shift" => binding.shift = true,
+                "super" | "logo" => binding.logo = true,
+                _ => return Err(format!("Unknown modifier: {}", modifier)),
+            }
+        }
+
+        Ok(binding)
+    }
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "Ctrl"),
+            (self.alt, "Alt"),
+            (self.shift, "Shift"),
+            (self.logo, "Super"),
+        ] {
+            if held {
+                write!(f, "{}+", name)?;
+            }
+        }
+        write!(f, "{}", xkb::keysym_get_name(self.keysym))
+    }
+}
+
+impl<'de> Deserialize<'de> for Binding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Keys {
+    pub fn action(&self, pressed: Binding) -> Option<KeyAction> {
+        self.0
+            .iter()
+            .find(|(binding, _)| binding.matches(&pressed))
+            .map(|(_, action)| *action)
+    }
+
+    fn new(mut keys: HashMap<KeyAction, Vec<Binding>>) -> Result<Self, String> {
+        for (action, names) in DEFAULT_KEYS {
+            keys.entry(*action).or_insert_with(|| {
+                names
+                    .iter()
+                    .map(|name| name.parse().expect("invalid default key"))
+                    .collect()
+            });
+        }
+
+        let mut keys: Vec<_> = keys.into_iter().collect();
+        keys.sort_by_key(|(action, _)| *action);
+
+        let mut bound: Vec<(Binding, KeyAction)> = Vec::new();
+        let mut conflicts = Vec::new();
+        for (action, bindings) in keys {
+            for binding in bindings {
+                for (other, other_action) in &bound {
+                    if other.overlaps(&binding) && *other_action != action {
+                        let keys = if *other == binding {
+                            other.to_string()
+                        } else {
+                            format!("{} and {}", other, binding)
+                        };
+                        conflicts.push(format!(
+                            "{} bound to both {:?} and {:?}",
+                            keys, other_action, action
+                        ));
+                    }
+                }
+                bound.push((binding, action));
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(Self(bound))
+        } else {
+            conflicts.sort();
+            Err(conflicts.join(", "))
+        }
+    }
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Self::new(HashMap::new()).expect("conflicting default keys")
+    }
+}
+
+impl<'de> Deserialize<'de> for Keys {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let keys = HashMap::deserialize(deserializer)?;
+        Self::new(keys).map_err(serde::de::Error::custom)
+    }
+}
+
+fn keysym_from_name(name: &str) -> Option<Keysym> {
+    if name.is_empty() || name.contains('\0') {
+        return None;
+    }
+
+    let keysym = xkb::keysym_from_name(name, xkb::KEYSYM_NO_FLAGS);
+    (keysym != Keysym::NoSymbol).then(|| lowercase(keysym))
+}
+
+// `Q` and `q` are the same key, Shift tells them apart
+fn lowercase(keysym: Keysym) -> Keysym {
+    match keysym.key_char() {
+        Some(c) if c.is_uppercase() => Keysym::from_char(c.to_lowercase().next().unwrap_or(c)),
+        _ => keysym,
+    }
+}
+
+fn uppercase(keysym: Keysym) -> Keysym {
+    match keysym.key_char() {
+        Some(c) if c.is_lowercase() => Keysym::from_char(c.to_uppercase().next().unwrap_or(c)),
+        _ => keysym,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Binding, KeyAction, Keys};
+    use smithay_client_toolkit::seat::keyboard::{Keysym, Modifiers};
+
+    #[test]
+    fn parse_bindings() {
+        let binding: Binding = "Ctrl+Shift+n".parse().unwrap();
+        assert_eq!(binding.to_string(), "Ctrl+Shift+n");
+        assert_eq!("Escape".parse::<Binding>().unwrap().to_string(), "Escape");
+
+        assert!("Ctrl+nope".parse::<Binding>().is_err());
+        assert!("Hyper+n".parse::<Binding>().is_err());
+        assert!("".parse::<Binding>().is_err());
+    }
+
+    #[test]
+    fn match_pressed_keys() {
+        let keys: Keys = toml::from_str(
+            r#"
+            close = ["Ctrl+w"]
+            clock = ["Shift+c", "question"]
+            "#,
+        )
+        .unwrap();
+
+        let none = Modifiers::default();
+        let ctrl = Modifiers { ctrl: true, ..none };
+        let shift = Modifiers {
+            shift: true,
+            ..none
+        };
+
+        let action = |keysym, modifiers| keys.action(Binding::pressed(keysym, modifiers));
+
+        assert_eq!(action(Keysym::w, ctrl), Some(KeyAction::Close));
+        // Replaced, not added to
+        assert_eq!(action(Keysym::Escape, none), None);
+        assert_eq!(action(Keysym::C, shift), Some(KeyAction::Clock));
+        assert_eq!(action(Keysym::c, none), None);
+        assert_eq!(action(Keysym::question, shift), Some(KeyAction::Clock));
+        // The defaults stay for the rest
+        assert_eq!(
+            action(Keysym::space, none),
+            Some(KeyAction::StopwatchToggle)
+        );
+    }
+
+    #[test]
+    fn reject_bad_keys() {
+        assert!(toml::from_str::<Keys>(r#"close = ["Esc"]"#).is_err());
+        assert!(toml::from_str::<Keys>(r#"close = ["l"]"#).is_err());
+        assert!(toml::from_str::<Keys>(r#"snooze = ["z"]"#).is_err());
+    }
+
+    #[test]
+    fn report_conflicts() {
+        // Both match Shift+question
+        let conflict = |toml| {
+            toml::from_str::<Keys>(toml)
+                .unwrap_err()
+                .message()
+                .to_owned()
+        };
+        assert_eq!(
+            conflict(
+                r#"
+                clock = ["question"]
+                world = ["Shift+question"]
+                "#
+            ),
+            "question and Shift+question bound to both Clock and World"
+        );
+        // Letters do need Shift
+        assert!(toml::from_str::<Keys>(r#"clock = ["c", "Shift+c"]"#).is_ok());
+
+        // Sorted, whatever order the table comes in
+        assert_eq!(
+            conflict(
+                r#"
+                timer = ["r"]
+                close = ["l", "space"]
+                "#
+            ),
+            "l bound to both Close and StopwatchLap, \
+             r bound to both Timer and StopwatchReset, \
+             space bound to both Close and StopwatchToggle"
+        );
+    }
+}
@@ -1,108 +1,505 @@
+mod alarm;
+mod animation;
+mod autohide;
+mod backend;
+mod blur;
+mod buffers;
+mod calendar;
 mod canvas;
 mod command;
+mod composite;
+mod config;
+mod countdown;
+mod damage;
 pub mod flock;
+mod hook;
 pub mod ipc;
+mod keys;
 mod lifecycle;
+mod peek;
+mod pomodoro;
 mod registry;
+mod stopwatch;
+mod subscribe;
 mod theme;
+pub mod wallclock;
 mod wayland;
 
-pub use canvas::Canvas;
-pub use command::Command;
+pub use alarm::Alarm;
+pub use canvas::{Background, Canvas, Contents};
+pub use command::{Command, Invocation};
+pub use config::{Blending, Config, Renderer, TimerStyle};
+pub use countdown::Countdown;
 pub use lifecycle::{Action, Event, State, View};
-pub use theme::{Bgra, Theme};
+pub use pomodoro::{Phase, Pomodoro, PomodoroAction};
+pub use stopwatch::{Stopwatch, StopwatchAction};
+pub use theme::{Bgra, Theme, ThemeName};
 pub use wayland::Wayland;
 
-use chrono::{Datelike, Local, Timelike};
-use smithay_client_toolkit::shell::WaylandSurface;
-use wayland_client::{QueueHandle, protocol::wl_shm::Format};
+use animation::Animation;
+use config::CloseOnClick;
+use ipc::{Notice, Payload, Reply};
+use peek::Peek;
+use subscribe::Subscriber;
+
+use chrono::{
+    DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc,
+};
+use chrono_tz::Tz;
+use smithay_client_toolkit::{
+    reexports::calloop::{
+        LoopHandle, RegistrationToken,
+        timer::{TimeoutAction, Timer},
+    },
+    seat::keyboard::Modifiers,
+    shell::WaylandSurface,
+};
+use std::time::{Duration, Instant};
+use wayland_client::QueueHandle;
 
 pub const SIDE: i32 = 448;
 
+// Times the surface blinks when a timer expires, on and off
+const FLASHES: u8 = 8;
+
 pub struct Sometime {
     pub wl: Wayland,
     canvas: Canvas,
+    config: Config,
+    theme: ThemeName,
     pub state: State,
+    started: Instant,
+    // Name of the output the surface entered last
+    pub(crate) output: Option<String>,
+    // Time zone for the clock view, the local one when unset
+    tz: Option<Tz>,
+    // Re-read whenever `/etc/localtime` changes
+    local_tz: Option<Tz>,
     pub last_second: u32,
     pub last_day: u32,
     pub is_happening: bool,
-    pub(crate) exit_on_release: bool,
+    pub(crate) peek: Option<Peek>,
+    // Every popup a peek, from the deprecated `--exit-on-release`
+    pub always_peek: bool,
+    loop_handle: LoopHandle<'static, Self>,
+    // Running timers, the next one to expire first
+    countdowns: Vec<Countdown>,
+    last_expired: Option<String>,
+    flashes: u8,
+    pub stopwatch: Stopwatch,
+    alarms: Vec<Alarm>,
+    // The timer for the next alarm to ring
+    alarm_token: Option<RegistrationToken>,
+    last_rung: Option<NaiveDateTime>,
+    // Label of the alarm on screen, until the popup closes
+    ringing: Option<String>,
+    pub pomodoro: Pomodoro,
+    // Connections streaming notices, from `subscribe`
+    subscribers: Vec<Subscriber>,
+    // The `--timeout` of the command that opened the popup
+    hide_after: Option<Duration>,
+    hide_token: Option<RegistrationToken>,
+    // Closes a peek that never got keyboard focus
+    peek_token: Option<RegistrationToken>,
+    // Months away from the current one on the calendar
+    month_offset: i32,
+    selected: Option<NaiveDate>,
+    // Top, right, bottom and left, moved by dragging the popup
+    margin: [i32; 4],
+    // Where the pointer grabbed the popup while dragging it
+    pub(crate) drag: Option<(f64, f64)>,
+    // Scrolled over the calendar but not yet a whole month
+    pub(crate) scroll: f64,
+    pub(crate) modifiers: Modifiers,
+    pub(crate) touch: Option<Touch>,
+    // From 0 when hidden to 1 when fully shown
+    openness: f32,
+    animation: Option<Animation>,
+}
+
+// The finger on the surface, from where it went down to where it is
+pub(crate) struct Touch {
+    pub id: i32,
+    pub start: (f64, f64),
+    pub last: (f64, f64),
 }
 
 impl Sometime {
-    pub fn new(wl: Wayland, exit_on_release: bool) -> Self {
+    pub fn new(wl: Wayland, config: Config, loop_handle: LoopHandle<'static, Self>) -> Self {
+        let margin = [config.surface.margin; 4];
+
         Self {
             wl,
-            canvas: Canvas::new(SIDE),
+            canvas: Canvas::new(SIDE, &config),
+            config,
+            theme: ThemeName::default(),
             state: State::Sleep,
+            started: Instant::now(),
+            output: None,
+            tz: None,
+            local_tz: wallclock::local_tz(),
             last_second: u32::MAX,
             last_day: u32::MAX,
             is_happening: false,
-            exit_on_release,
+            peek: None,
+            always_peek: false,
+            loop_handle,
+            countdowns: Vec::new(),
+            last_expired: None,
+            flashes: 0,
+            stopwatch: Stopwatch::default(),
+            alarms: Vec::new(),
+            alarm_token: None,
+            last_rung: None,
+            ringing: None,
+            pomodoro: Pomodoro::default(),
+            subscribers: Vec::new(),
+            hide_after: None,
+            hide_token: None,
+            peek_token: None,
+            month_offset: 0,
+            selected: None,
+            margin,
+            drag: None,
+            scroll: 0.0,
+            modifiers: Modifiers::default(),
+            touch: None,
+            openness: 0.0,
+            animation: None,
+        }
+    }
+
+    pub fn command(
+        &mut self,
+        invocation: Invocation,
+        qh: &QueueHandle<Self>,
+    ) -> anyhow::Result<Reply> {
+        let Invocation {
+            command: cmd,
+            timeout,
+            peek,
+        } = invocation;
+
+        match &cmd {
+            Command::Clock(tz) if self.state == State::Sleep => self.tz = *tz,
+            // The local clock, unless it's already showing another zone
+            Command::Show(View::Clock) | Command::Switch(View::Clock)
+                if !matches!(
+                    self.state,
+                    State::WakeUp(View::Clock)
+                        | State::Opening(View::Clock)
+                        | State::Awake(View::Clock)
+                ) =>
+            {
+                self.tz = None;
+            }
+            Command::StartTimer { duration, name } => {
+                return self.start_countdown(*duration, name.clone(), qh);
+            }
+            Command::CancelTimer(name) => return self.cancel_countdown(name),
+            Command::ListTimers => return Ok(Reply::State(self.list_countdowns())),
+            Command::Stopwatch(Some(action)) => {
+                return Ok(Reply::State(self.stopwatch(*action)));
+            }
+            Command::AddAlarm { time, days, label } => {
+                return self.add_alarm(*time, days.clone(), label.clone(), qh);
+            }
+            Command::RemoveAlarm(id) => return self.remove_alarm(*id, qh),
+            Command::ListAlarms => return Ok(Reply::State(self.list_alarms())),
+            Command::Pomodoro(action) => return Ok(Reply::State(self.pomodoro(*action, qh)?)),
+            Command::Status => return Ok(Reply::State(self.status())),
+            Command::Subscribe => anyhow::bail!("subscribe needs a connection of its own"),
+            _ => {}
+        }
+
+        if let Some(event) = cmd.event() {
+            if timeout.is_some() {
+                self.hide_after = timeout;
+            }
+            if (peek || self.always_peek) && self.state == State::Sleep {
+                self.start_peek(qh);
+            }
+            self.handle(event, qh);
+
+            if timeout.is_some() {
+                self.reset_hide_timer(qh);
+            }
+        }
+
+        Ok(Reply::Ok)
+    }
+
+    fn status(&self) -> Payload {
+        Payload::Status {
+            state: self.state,
+            output: self.output.clone(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            theme: self.theme,
+            config: Config::path(),
+            timers: self.countdowns.iter().map(Countdown::timer_state).collect(),
+            alarms: self.alarms.clone(),
         }
     }
 
     pub fn handle(&mut self, event: Event, qh: &QueueHandle<Self>) {
         let (state, action) = self.state.and_then(event);
 
+        if state != self.state {
+            self.notify(Notice::State { state });
+        }
         self.state = state;
 
         match action {
-            Action::CreateLayer => self.wl.create_layer(qh, "sometime"),
-            Action::Draw => self.draw(),
-            Action::DestroyLayer => self.wl.destroy_layer(),
+            Action::CreateLayer => {
+                self.wl
+                    .create_layer(qh, "sometime", self.config.surface.anchor, self.margin)
+            }
+            Action::Draw => {
+                self.draw();
+                self.reset_hide_timer(qh);
+            }
+            Action::Animate => self.animate(qh),
+            Action::DestroyLayer => {
+                self.wl.destroy_layer();
+                self.ringing = None;
+                self.output = None;
+                self.hide_after = None;
+                self.cancel_hide_timer();
+                self.month_offset = 0;
+                self.selected = None;
+                self.drag = None;
+                self.scroll = 0.0;
+                self.touch = None;
+                self.peek = None;
+                self.cancel_peek_timer();
+                self.openness = 0.0;
+                self.stop_animation();
+            }
             Action::Vanish => self.wl.exit = true,
             Action::Ignore => {}
         }
     }
 
     pub fn draw(&mut self) {
-        let State::Awake(view) = self.state else {
+        let Some(view) = self.state.visible() else {
             return;
         };
+        let Some(layer) = self.wl.layer.as_ref() else {
+            return;
+        };
+
+        let utc = Utc::now();
+        let now = self.local_time(utc);
+        let month = self.calendar_month();
+        let transition = self.transition();
 
-        let now = Local::now();
+        // Drawn straight into the buffer the compositor reads
+        let Some((pixels, contents)) = self.wl.buffers.next() else {
+            return;
+        };
+        let canvas = &mut self.canvas;
 
         match view {
             View::Clock => {
-                self.canvas
-                    .pixel_data
-                    .copy_from_slice(&self.canvas.clock_bg_cache);
-                self.canvas
-                    .draw_clock_hands(now.hour(), now.minute(), now.second());
+                let time = match self.tz {
+                    Some(tz) => utc.with_timezone(&tz).time(),
+                    None => now.time(),
+                };
+
+                canvas.clear(pixels, contents, Background::Clock);
+                canvas.draw_clock_hands(pixels, time.hour(), time.minute(), time.second());
+
+                if let Some(progress) = self.pomodoro.progress(&self.config.pomodoro) {
+                    canvas.draw_progress_ring(pixels, progress, self.pomodoro.phase.is_break());
+                }
+
+                if let Some(label) = &self.ringing {
+                    canvas.draw_clock_label(pixels, label);
+                } else if let Some(tz) = self.tz {
+                    canvas.draw_clock_label(pixels, &city(tz));
+                }
 
                 self.last_second = now.second();
             }
             View::Calendar => {
-                self.canvas
-                    .pixel_data
-                    .copy_from_slice(&self.canvas.calendar_bg_cache);
-                self.canvas
-                    .draw_calendar_fonts(now.year(), now.month(), now.day());
+                let shown = |date: NaiveDate| {
+                    (date.year() == month.year() && date.month() == month.month())
+                        .then(|| date.day())
+                };
+
+                canvas.show_month(month, shown(now.date()));
+                canvas.clear(pixels, contents, Background::Calendar);
+                canvas.draw_calendar_fonts(pixels, self.selected.and_then(shown));
 
                 self.last_day = now.day();
             }
+            View::World => {
+                let clocks: Vec<(String, NaiveTime)> =
+                    std::iter::once(("Local".into(), now.time()))
+                        .chain(
+                            self.config
+                                .world
+                                .iter()
+                                .map(|tz| (city(*tz), utc.with_timezone(tz).time())),
+                        )
+                        .collect();
+
+                canvas.clear(pixels, contents, Background::World);
+                canvas.draw_world_clocks(pixels, &clocks);
+
+                self.last_second = now.second();
+            }
+            View::Timer => {
+                let arc = self.config.timer.style == TimerStyle::Arc;
+
+                canvas.clear(pixels, contents, Background::Clock);
+
+                match self.countdowns.first() {
+                    Some(countdown) => canvas.draw_timer(
+                        pixels,
+                        arc.then(|| countdown.progress()),
+                        &countdown::format_remaining(countdown.remaining()),
+                        &countdown.name,
+                    ),
+                    None => canvas.draw_timer(
+                        pixels,
+                        arc.then_some(1.0),
+                        "0:00",
+                        self.last_expired.as_deref().unwrap_or("no timers"),
+                    ),
+                }
+
+                self.last_second = now.second();
+            }
+            View::Stopwatch => {
+                let laps: Vec<String> = self
+                    .stopwatch
+                    .laps()
+                    .take(3)
+                    .map(|(n, lap)| format!("Lap {}  {}", n, stopwatch::format_elapsed(lap)))
+                    .collect();
+
+                canvas.clear(pixels, contents, Background::Clock);
+                canvas.draw_stopwatch(
+                    pixels,
+                    &stopwatch::format_elapsed(self.stopwatch.elapsed()),
+                    &laps,
+                );
+            }
+        }
+
+        if self.flashes % 2 == 1 {
+            canvas.flash(pixels);
+        }
+        if let Some((scale, offset, opacity)) = transition {
+            canvas.transition(pixels, scale, offset, opacity);
         }
 
-        self.update_surface();
+        let damage = canvas.finish(contents);
+        self.wl.buffers.attach(layer.wl_surface(), &damage);
+
+        // Not while it moves, the blur would lag behind
+        let backdrop = if self.config.surface.blur && transition.is_none() {
+            canvas.backdrop()
+        } else {
+            Vec::new()
+        };
+        self.wl.blur.set(&self.wl.compositor, backdrop);
+        layer.commit();
     }
 
-    fn update_surface(&mut self) {
-        let side = self.canvas.side;
-        let stride = side * 4;
+    // A click or tap on the surface, selecting a day or closing the popup
+    pub(crate) fn click(&mut self, x: f64, y: f64, qh: &QueueHandle<Self>) {
+        if self.select_day_at(x, y) {
+            return;
+        }
 
-        if let Some(layer) = self.wl.layer.as_ref()
-            && let Ok((buffer, surface)) =
-                self.wl
-                    .pool
-                    .create_buffer(side, side, stride, Format::Argb8888)
-        {
-            surface.copy_from_slice(&self.canvas.pixel_data);
-
-            let wl_surface = layer.wl_surface();
-            wl_surface.damage_buffer(0, 0, side, side);
-            buffer.attach_to(wl_surface).ok();
-            layer.commit();
+        if matches!(
+            self.config.pointer.close_on_click,
+            CloseOnClick::Inside | CloseOnClick::Anywhere
+        ) {
+            self.handle(Event::Hide, qh);
+        }
+    }
+
+    // A tap picks a day or closes, as there's no focus to lose by tapping
+    // elsewhere and so no `close_on_click` to respect
+    pub(crate) fn tap(&mut self, x: f64, y: f64, qh: &QueueHandle<Self>) {
+        if !self.select_day_at(x, y) {
+            self.handle(Event::Hide, qh);
+        }
+    }
+
+    // Blink the surface a few times
+    fn flash(&mut self) {
+        let flashing = self.flashes > 0;
+        self.flashes = FLASHES;
+
+        if flashing {
+            return;
+        }
+
+        self.loop_handle
+            .insert_source(Timer::immediate(), |_, _, app| {
+                app.flashes -= 1;
+                app.draw();
+
+                if app.flashes == 0 {
+                    TimeoutAction::Drop
+                } else {
+                    TimeoutAction::ToDuration(Duration::from_millis(250))
+                }
+            })
+            .ok();
+    }
+
+    // Redraw right away after the wall clock jumped, and re-arm the alarms
+    // against it
+    pub fn clock_changed(&mut self, qh: &QueueHandle<Self>) {
+        self.last_second = u32::MAX;
+        self.last_day = u32::MAX;
+        self.draw();
+        self.schedule_alarms(qh);
+    }
+
+    // Pick up a new local time zone and redraw with it
+    pub fn tz_changed(&mut self, qh: &QueueHandle<Self>) {
+        self.local_tz = wallclock::local_tz();
+        self.clock_changed(qh);
+    }
+
+    pub fn local_now(&self) -> NaiveDateTime {
+        self.local_time(Utc::now())
+    }
+
+    fn local_time(&self, utc: DateTime<Utc>) -> NaiveDateTime {
+        match self.local_tz {
+            Some(tz) => utc.with_timezone(&tz).naive_local(),
+            None => utc.with_timezone(&Local).naive_local(),
+        }
+    }
+
+    // The earliest instant for a local time, skipping ahead past DST gaps
+    fn local_to_utc(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        let after_gap = local + chrono::TimeDelta::hours(1);
+
+        match self.local_tz {
+            Some(tz) => tz
+                .from_local_datetime(&local)
+                .earliest()
+                .or_else(|| tz.from_local_datetime(&after_gap).earliest())
+                .map(|at| at.to_utc()),
+            None => Local
+                .from_local_datetime(&local)
+                .earliest()
+                .or_else(|| Local.from_local_datetime(&after_gap).earliest())
+                .map(|at| at.to_utc()),
         }
     }
 }
+
+// `America/Sao_Paulo` -> `Sao Paulo`
+fn city(tz: Tz) -> String {
+    let name = tz.name();
+    name.rsplit('/').next().unwrap_or(name).replace('_', " ")
+}
@@ -1,27 +1,47 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "view", rename_all = "snake_case")]
 pub enum State {
     // No surface, the daemon idles waiting for commands
     Sleep,
     // A layer surface was requested, waiting for the compositor to
     /// configure it before drawing
     WakeUp(View),
+    // Animating in, one frame callback at a time
+    Opening(View),
     // The view is on screen, redrawn as time passes
     Awake(View),
+    // Animating out before the surface goes away
+    Closing(View),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum View {
     Clock,
     Calendar,
+    World,
+    Timer,
+    Stopwatch,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Event {
-    // The `clock` or `calendar` command
+    // A view command such as `clock` or `calendar`, or an expired timer
     Toggle(View),
+    // `show`, opening the view or switching to it
+    Show(View),
+    // `hide`, a close key, a click, the timeout or releasing the keys of a
+    // `--peek`, animating out whatever is open
+    Hide,
+    // `switch`, changing the view only if one is open
+    Switch(View),
     // The compositor acknowledged the layer surface
     Configure,
-    // `esc`/`q`, a key release under `--exit-on-release`, or the compositor closing the surface
+    // The opening or closing animation reached its end
+    Animated,
+    // The compositor closing the surface, gone at once
     Close,
     // The `dismiss` command
     Quit,
@@ -31,19 +51,99 @@ pub enum Event {
 pub enum Action {
     CreateLayer,
     Draw,
+    // Start, or turn back, the animation for the new state
+    Animate,
     DestroyLayer,
     Vanish,
     Ignore,
 }
 
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sleep => write!(f, "asleep"),
+            Self::WakeUp(view) => write!(f, "waking up to the {}", view),
+            Self::Opening(view) => write!(f, "opening the {}", view),
+            Self::Awake(view) => write!(f, "showing the {}", view),
+            Self::Closing(view) => write!(f, "closing the {}", view),
+        }
+    }
+}
+
+impl std::fmt::Display for View {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Clock => write!(f, "clock"),
+            Self::Calendar => write!(f, "calendar"),
+            Self::World => write!(f, "world"),
+            Self::Timer => write!(f, "timer"),
+            Self::Stopwatch => write!(f, "stopwatch"),
+        }
+    }
+}
+
+impl std::str::FromStr for View {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clock" => Ok(Self::Clock),
+            "calendar" => Ok(Self::Calendar),
+            "world" => Ok(Self::World),
+            "timer" => Ok(Self::Timer),
+            "stopwatch" => Ok(Self::Stopwatch),
+            _ => Err(format!("Unknown view: {}", s)),
+        }
+    }
+}
+
 impl State {
+    // The view on the surface, while there's something to draw
+    pub fn visible(self) -> Option<View> {
+        match self {
+            Self::Opening(view) | Self::Awake(view) | Self::Closing(view) => Some(view),
+            Self::Sleep | Self::WakeUp(_) => None,
+        }
+    }
+
     pub fn and_then(self, event: Event) -> (Self, Action) {
         match (self, event) {
-            // The lifecycle: Sleep -> WakeUp -> Awake -> Sleep
-            (Self::Sleep, Event::Toggle(view)) => (Self::WakeUp(view), Action::CreateLayer),
-            (Self::WakeUp(view), Event::Configure) => (Self::Awake(view), Action::Draw),
-            (Self::Awake(current_view), Event::Toggle(view)) if view == current_view => {
-                (Self::Sleep, Action::DestroyLayer)
+            // The lifecycle: Sleep -> WakeUp -> Opening -> Awake -> Closing -> Sleep
+            (Self::Sleep, Event::Toggle(view) | Event::Show(view)) => {
+                (Self::WakeUp(view), Action::CreateLayer)
+            }
+            (Self::WakeUp(view), Event::Configure) => (Self::Opening(view), Action::Animate),
+            (Self::Opening(view), Event::Animated) => (Self::Awake(view), Action::Draw),
+            (Self::Opening(current_view) | Self::Awake(current_view), Event::Toggle(view))
+                if view == current_view =>
+            {
+                (Self::Closing(view), Action::Animate)
+            }
+            (Self::Closing(_), Event::Animated) => (Self::Sleep, Action::DestroyLayer),
+
+            // Asked for again while closing, turn back
+            (Self::Closing(_), Event::Toggle(view) | Event::Show(view)) => {
+                (Self::Opening(view), Action::Animate)
+            }
+
+            // Explicit commands, changing the view in place
+            (Self::WakeUp(_), Event::Show(view) | Event::Switch(view)) => {
+                (Self::WakeUp(view), Action::Ignore)
+            }
+            // The next frame of the animation draws it
+            (Self::Opening(current_view), Event::Show(view) | Event::Switch(view))
+                if view != current_view =>
+            {
+                (Self::Opening(view), Action::Ignore)
+            }
+            (Self::Awake(current_view), Event::Show(view) | Event::Switch(view))
+                if view != current_view =>
+            {
+                (Self::Awake(view), Action::Draw)
+            }
+            (Self::WakeUp(_), Event::Hide) => (Self::Sleep, Action::DestroyLayer),
+            (Self::Opening(view) | Self::Awake(view), Event::Hide) => {
+                (Self::Closing(view), Action::Animate)
             }
 
             // Close and quit regardless of current state
@@ -62,24 +162,57 @@ mod tests {
 
     #[test]
     fn lifecycle() {
-        // Clock
-        assert_eq!(Sleep.and_then(Toggle(Clock)), (WakeUp(Clock), CreateLayer));
-        assert_eq!(WakeUp(Clock).and_then(Configure), (Awake(Clock), Draw));
-        assert_eq!(Awake(Clock).and_then(Toggle(Clock)), (Sleep, DestroyLayer));
+        for view in [Clock, Calendar, World, Timer, Stopwatch] {
+            assert_eq!(Sleep.and_then(Toggle(view)), (WakeUp(view), CreateLayer));
+            assert_eq!(WakeUp(view).and_then(Configure), (Opening(view), Animate));
+            assert_eq!(Opening(view).and_then(Animated), (Awake(view), Draw));
+            assert_eq!(Awake(view).and_then(Toggle(view)), (Closing(view), Animate));
+            assert_eq!(Closing(view).and_then(Animated), (Sleep, DestroyLayer));
+        }
+    }
 
-        // Calendar
+    #[test]
+    fn toggle_during_animation() {
+        // Turn back halfway
         assert_eq!(
-            Sleep.and_then(Toggle(Calendar)),
-            (WakeUp(Calendar), CreateLayer)
+            Opening(Clock).and_then(Toggle(Clock)),
+            (Closing(Clock), Animate)
         );
         assert_eq!(
-            WakeUp(Calendar).and_then(Configure),
-            (Awake(Calendar), Draw)
+            Closing(Clock).and_then(Toggle(Clock)),
+            (Opening(Clock), Animate)
+        );
+        assert_eq!(
+            Closing(Clock).and_then(Show(Calendar)),
+            (Opening(Calendar), Animate)
+        );
+        assert_eq!(
+            Closing(Clock).and_then(Toggle(Calendar)),
+            (Opening(Calendar), Animate)
         );
+
+        // Like when awake
+        assert_eq!(
+            Opening(Clock).and_then(Toggle(Calendar)),
+            (Opening(Clock), Ignore)
+        );
+        assert_eq!(
+            Opening(Clock).and_then(Switch(Calendar)),
+            (Opening(Calendar), Ignore)
+        );
+        assert_eq!(Opening(Clock).and_then(Hide), (Closing(Clock), Animate));
+
+        // Already on the way out
+        assert_eq!(Closing(Clock).and_then(Hide), (Closing(Clock), Ignore));
         assert_eq!(
-            Awake(Calendar).and_then(Toggle(Calendar)),
-            (Sleep, DestroyLayer)
+            Closing(Clock).and_then(Switch(Calendar)),
+            (Closing(Clock), Ignore)
         );
+
+        // Stray events
+        assert_eq!(Opening(Clock).and_then(Configure), (Opening(Clock), Ignore));
+        assert_eq!(Awake(Clock).and_then(Animated), (Awake(Clock), Ignore));
+        assert_eq!(Sleep.and_then(Animated), (Sleep, Ignore));
     }
 
     #[test]
@@ -135,6 +268,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn show_opens_or_switches() {
+        assert_eq!(
+            Sleep.and_then(Show(Calendar)),
+            (WakeUp(Calendar), CreateLayer)
+        );
+        assert_eq!(
+            WakeUp(Clock).and_then(Show(Calendar)),
+            (WakeUp(Calendar), Ignore)
+        );
+        assert_eq!(
+            WakeUp(Calendar).and_then(Show(Calendar)),
+            (WakeUp(Calendar), Ignore)
+        );
+        assert_eq!(
+            Awake(Clock).and_then(Show(Calendar)),
+            (Awake(Calendar), Draw)
+        );
+        assert_eq!(
+            Awake(Calendar).and_then(Show(Calendar)),
+            (Awake(Calendar), Ignore)
+        );
+    }
+
+    #[test]
+    fn switch_only_when_open() {
+        assert_eq!(Sleep.and_then(Switch(World)), (Sleep, Ignore));
+        assert_eq!(
+            WakeUp(Clock).and_then(Switch(World)),
+            (WakeUp(World), Ignore)
+        );
+        assert_eq!(
+            WakeUp(World).and_then(Switch(World)),
+            (WakeUp(World), Ignore)
+        );
+        assert_eq!(Awake(Timer).and_then(Switch(World)), (Awake(World), Draw));
+        assert_eq!(Awake(World).and_then(Switch(World)), (Awake(World), Ignore));
+    }
+
+    #[test]
+    fn hide_only_when_open() {
+        assert_eq!(Sleep.and_then(Hide), (Sleep, Ignore));
+        assert_eq!(WakeUp(Stopwatch).and_then(Hide), (Sleep, DestroyLayer));
+        assert_eq!(
+            Awake(Stopwatch).and_then(Hide),
+            (Closing(Stopwatch), Animate)
+        );
+    }
+
     #[test]
     fn always_destroy_layer_and_sleep_after_close() {
         // Sleep
@@ -147,6 +329,10 @@ mod tests {
         // Calendar
         assert_eq!(WakeUp(Calendar).and_then(Close), (Sleep, DestroyLayer));
         assert_eq!(Awake(Calendar).and_then(Close), (Sleep, DestroyLayer));
+
+        // Animating
+        assert_eq!(Opening(Clock).and_then(Close), (Sleep, DestroyLayer));
+        assert_eq!(Closing(Clock).and_then(Close), (Sleep, DestroyLayer));
     }
 
     #[test]
@@ -0,0 +1,206 @@
+use smithay_client_toolkit::{
+    reexports::calloop::timer::{TimeoutAction, Timer},
+    seat::keyboard::Modifiers,
+};
+use std::time::Duration;
+use wayland_client::QueueHandle;
+
+use crate::{Event, Sometime};
+
+// How long a peek waits for keyboard focus to tell what's held, before
+// there's no knowing when it's let go and it closes
+const FOCUS_WAIT: Duration = Duration::from_secs(1);
+
+// A popup opened with `--peek`
+#[derive(Debug)]
+pub(crate) enum Peek {
+    // Until keyboard focus tells which keys are held, and the modifiers
+    // event right after it which ones of those are modifiers
+    Pending {
+        // Raw key codes from `enter`, none before it
+        keys: Option<Vec<u32>>,
+    },
+    // What's still down of the binding that opened it
+    Holding {
+        // Raw key codes
+        keys: Vec<u32>,
+        // Ctrl, Alt, Shift and Super, but not the locks
+        modifiers: Modifiers,
+    },
+}
+
+impl Peek {
+    // Focus came with these keys down; what's held is only known with the
+    // modifiers that follow
+    pub fn enter(&mut self, keys: &[u32]) {
+        if let Self::Pending { keys: entered } = self {
+            *entered = Some(keys.to_vec());
+        }
+    }
+
+    // A key went up; one that wasn't held to open the popup changes nothing
+    pub fn release_key(&mut self, key: u32) -> bool {
+        match self {
+            Self::Pending {
+                keys: Some(keys), ..
+            } => {
+                keys.retain(|held| *held != key);
+                false
+            }
+            Self::Holding { keys, .. } if keys.contains(&key) => {
+                keys.retain(|held| *held != key);
+                self.released()
+            }
+            _ => false,
+        }
+    }
+
+    // Modifiers changed; the first time after focus they're what's held,
+    // after that only those let go count, not new ones; true when nothing
+    // is held anymore, so the popup closes
+    pub fn update_modifiers(&mut self, now: Modifiers) -> bool {
+        match self {
+            Self::Pending { keys: Some(keys) } => {
+                *self = Self::Holding {
+                    keys: std::mem::take(keys),
+                    modifiers: held(now, now),
+                };
+            }
+            Self::Holding { modifiers, .. } => *modifiers = held(*modifiers, now),
+            Self::Pending { keys: None } => {}
+        }
+        self.released()
+    }
+
+    fn released(&self) -> bool {
+        match self {
+            Self::Holding { keys, modifiers } => {
+                let Modifiers {
+                    ctrl,
+                    alt,
+                    shift,
+                    logo,
+                    ..
+                } = *modifiers;
+                keys.is_empty() && !(ctrl || alt || shift || logo)
+            }
+            Self::Pending { .. } => false,
+        }
+    }
+}
+
+// Modifiers down in both
+fn held(before: Modifiers, now: Modifiers) -> Modifiers {
+    Modifiers {
+        ctrl: before.ctrl && now.ctrl,
+        alt: before.alt && now.alt,
+        shift: before.shift && now.shift,
+        logo: before.logo && now.logo,
+        ..Modifiers::default()
+    }
+}
+
+impl Sometime {
+    // Wait for focus to tell which keys opened the popup
+    pub(crate) fn start_peek(&mut self, qh: &QueueHandle<Self>) {
+        self.cancel_peek_timer();
+        self.peek = Some(Peek::Pending { keys: None });
+
+        let qh = qh.clone();
+        let token =
+            self.loop_handle
+                .insert_source(Timer::from_duration(FOCUS_WAIT), move |_, _, app| {
+                    app.peek_token = None;
+                    if matches!(app.peek, Some(Peek::Pending { .. })) {
+                        app.handle(Event::Hide, &qh);
+                    }
+                    TimeoutAction::Drop
+                });
+
+        match token {
+            Ok(token) => self.peek_token = Some(token),
+            Err(e) => eprintln!("Can't schedule closing the peek: {}", e.error),
+        }
+    }
+
+    pub(crate) fn cancel_peek_timer(&mut self) {
+        if let Some(token) = self.peek_token.take() {
+            self.loop_handle.remove(token);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Peek;
+    use smithay_client_toolkit::seat::keyboard::Modifiers;
+
+    const SUPER: Modifiers = Modifiers {
+        ctrl: false,
+        alt: false,
+        shift: false,
+        caps_lock: false,
+        logo: true,
+        num_lock: false,
+    };
+    const NONE: Modifiers = Modifiers {
+        logo: false,
+        ..SUPER
+    };
+    // Super_L and C
+    const LOGO: u32 = 125;
+    const C: u32 = 46;
+
+    // Focus as the compositor gives it, the keys and then the modifiers
+    fn focus(keys: &[u32], modifiers: Modifiers) -> (Peek, bool) {
+        let mut peek = Peek::Pending { keys: None };
+        peek.enter(keys);
+        let released = peek.update_modifiers(modifiers);
+        (peek, released)
+    }
+
+    #[test]
+    fn closes_once_held_keys_go_up() {
+        let mut peek = Peek::Pending { keys: None };
+        assert!(!peek.release_key(C));
+        assert!(!peek.update_modifiers(NONE));
+
+        let (mut peek, released) = focus(&[LOGO, C], SUPER);
+        assert!(!released);
+        assert!(!peek.release_key(C));
+        assert!(!peek.release_key(LOGO));
+        assert!(peek.update_modifiers(NONE));
+    }
+
+    #[test]
+    fn ignores_unrelated_keys() {
+        let (mut peek, released) = focus(&[C], NONE);
+        assert!(!released);
+        // A key pressed and released while peeking
+        assert!(!peek.release_key(30));
+        assert!(!peek.update_modifiers(Modifiers {
+            shift: true,
+            ..NONE
+        }));
+        assert!(!peek.update_modifiers(NONE));
+        assert!(peek.release_key(C));
+    }
+
+    #[test]
+    fn empty_key_list() {
+        // Only the modifiers told, as some compositors do; whatever was
+        // known of them before focus doesn't count
+        let mut peek = Peek::Pending { keys: None };
+        peek.enter(&[]);
+        assert!(!peek.update_modifiers(SUPER));
+        assert!(!peek.release_key(C));
+        assert!(peek.update_modifiers(NONE));
+
+        // Let go before focus came, or locks that stay on
+        let caps = Modifiers {
+            caps_lock: true,
+            ..NONE
+        };
+        assert!(focus(&[], caps).1);
+    }
+}
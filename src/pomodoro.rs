@@ -0,0 +1,296 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use smithay_client_toolkit::reexports::calloop::{
+    RegistrationToken,
+    timer::{TimeoutAction, Timer},
+};
+use std::time::{Duration, Instant};
+use wayland_client::QueueHandle;
+
+use crate::{Event, Sometime, State, View, config, config::PomodoroConfig, hook, ipc::Payload};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Phase {
+    #[default]
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PomodoroAction {
+    Start,
+    Pause,
+    Skip,
+    Status,
+}
+
+#[derive(Debug, Default)]
+pub struct Pomodoro {
+    pub phase: Phase,
+    // Work sessions finished so far
+    pub completed: u32,
+    // Time left in the phase while paused, `None` before the first start
+    remaining: Option<Duration>,
+    // The end of the phase while running
+    running: Option<(Instant, RegistrationToken)>,
+}
+
+// The layout of `pomodoro.toml`
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedPomodoro {
+    phase: Phase,
+    completed: u32,
+    remaining_secs: Option<u64>,
+}
+
+impl Phase {
+    // The phase after this one, once `completed` work sessions are done
+    pub fn next(self, completed: u32, cycles: u32) -> Self {
+        match self {
+            Self::Work if completed > 0 && completed.is_multiple_of(cycles.max(1)) => {
+                Self::LongBreak
+            }
+            Self::Work => Self::ShortBreak,
+            Self::ShortBreak | Self::LongBreak => Self::Work,
+        }
+    }
+
+    pub fn is_break(self) -> bool {
+        self != Self::Work
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Work => write!(f, "work"),
+            Self::ShortBreak => write!(f, "short break"),
+            Self::LongBreak => write!(f, "long break"),
+        }
+    }
+}
+
+impl Pomodoro {
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn remaining(&self, config: &PomodoroConfig) -> Duration {
+        match self.running {
+            Some((deadline, _)) => deadline.saturating_duration_since(Instant::now()),
+            None => self
+                .remaining
+                .unwrap_or_else(|| config.duration(self.phase)),
+        }
+    }
+
+    // How much of the phase already went by, `None` until it's first started
+    pub fn progress(&self, config: &PomodoroConfig) -> Option<f32> {
+        if self.running.is_none() && self.remaining.is_none() {
+            return None;
+        }
+
+        let total = config.duration(self.phase).as_secs_f32();
+        Some(1.0 - self.remaining(config).as_secs_f32() / total)
+    }
+}
+
+impl std::str::FromStr for PomodoroAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(Self::Start),
+            "pause" => Ok(Self::Pause),
+            "skip" => Ok(Self::Skip),
+            "status" => Ok(Self::Status),
+            _ => Err(format!("Unknown pomodoro action: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for PomodoroAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Start => write!(f, "start"),
+            Self::Pause => write!(f, "pause"),
+            Self::Skip => write!(f, "skip"),
+            Self::Status => write!(f, "status"),
+        }
+    }
+}
+
+impl Sometime {
+    // Pick up the cycle where a previous run left it, paused
+    pub fn load_pomodoro(&mut self) -> anyhow::Result<()> {
+        let Some(path) = config::state_path("pomodoro.toml") else {
+            return Ok(());
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                let saved: SavedPomodoro = toml::from_str(&contents)
+                    .with_context(|| format!("invalid {}", path.display()))?;
+
+                self.pomodoro = Pomodoro {
+                    phase: saved.phase,
+                    completed: saved.completed,
+                    remaining: saved.remaining_secs.map(Duration::from_secs),
+                    running: None,
+                };
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("can't read {}", path.display())),
+        }
+    }
+
+    pub(crate) fn pomodoro(
+        &mut self,
+        action: PomodoroAction,
+        qh: &QueueHandle<Self>,
+    ) -> anyhow::Result<Payload> {
+        match action {
+            PomodoroAction::Start if self.pomodoro.is_running() => {}
+            PomodoroAction::Start => self.start_phase(qh)?,
+            PomodoroAction::Pause => {
+                if let Some((deadline, token)) = self.pomodoro.running.take() {
+                    self.loop_handle.remove(token);
+                    self.pomodoro.remaining =
+                        Some(deadline.saturating_duration_since(Instant::now()));
+                }
+            }
+            PomodoroAction::Skip => {
+                let running = self.pomodoro.is_running();
+                self.stop_phase();
+
+                // Skipped work doesn't count, nor earns a long break
+                let config = &self.config.pomodoro;
+                self.pomodoro.phase = match self.pomodoro.phase {
+                    Phase::Work => Phase::ShortBreak,
+                    phase => phase.next(self.pomodoro.completed, config.cycles),
+                };
+                self.pomodoro.remaining = Some(config.duration(self.pomodoro.phase));
+
+                if running {
+                    self.start_phase(qh)?;
+                }
+            }
+            PomodoroAction::Status => return Ok(self.pomodoro_status()),
+        }
+
+        self.save_pomodoro()?;
+
+        if self.state == State::Awake(View::Clock) {
+            self.draw();
+        }
+
+        Ok(self.pomodoro_status())
+    }
+
+    fn start_phase(&mut self, qh: &QueueHandle<Self>) -> anyhow::Result<()> {
+        let remaining = self.pomodoro.remaining(&self.config.pomodoro);
+        let Some(deadline) = Instant::now().checked_add(remaining) else {
+            anyhow::bail!("a phase of {}s is too long", remaining.as_secs());
+        };
+        let qh = qh.clone();
+        let token = self
+            .loop_handle
+            .insert_source(Timer::from_deadline(deadline), move |_, _, app| {
+                app.phase_over(&qh);
+                TimeoutAction::Drop
+            })
+            .map_err(|e| e.error)?;
+
+        self.pomodoro.running = Some((deadline, token));
+        Ok(())
+    }
+
+    fn stop_phase(&mut self) {
+        if let Some((_, token)) = self.pomodoro.running.take() {
+            self.loop_handle.remove(token);
+        }
+    }
+
+    // Move on to the next phase and keep the cycle going
+    fn phase_over(&mut self, qh: &QueueHandle<Self>) {
+        self.pomodoro.running = None;
+
+        if self.pomodoro.phase == Phase::Work {
+            self.pomodoro.completed += 1;
+        }
+
+        let config = &self.config.pomodoro;
+        self.pomodoro.phase = self
+            .pomodoro
+            .phase
+            .next(self.pomodoro.completed, config.cycles);
+        self.pomodoro.remaining = Some(config.duration(self.pomodoro.phase));
+
+        if let Some(command) = &config.command {
+            hook::run(
+                command,
+                &[("SOMETIME_POMODORO", &self.pomodoro.phase.to_string())],
+            );
+        }
+
+        if let Err(e) = self.start_phase(qh) {
+            eprintln!("Can't start the next pomodoro phase: {}", e);
+        }
+        if let Err(e) = self.save_pomodoro() {
+            eprintln!("Can't save the pomodoro: {}", e);
+        }
+
+        match self.state {
+            State::Sleep => {
+                self.tz = None;
+                self.handle(Event::Toggle(View::Clock), qh);
+            }
+            _ => self.draw(),
+        }
+
+        self.flash();
+    }
+
+    fn pomodoro_status(&self) -> Payload {
+        Payload::Pomodoro {
+            phase: self.pomodoro.phase,
+            running: self.pomodoro.is_running(),
+            remaining_secs: self.pomodoro.remaining(&self.config.pomodoro).as_secs(),
+            completed: self.pomodoro.completed,
+        }
+    }
+
+    fn save_pomodoro(&self) -> anyhow::Result<()> {
+        let Some(path) = config::state_path("pomodoro.toml") else {
+            anyhow::bail!("can't find a state directory to save the pomodoro in");
+        };
+
+        let saved = SavedPomodoro {
+            phase: self.pomodoro.phase,
+            completed: self.pomodoro.completed,
+            remaining_secs: (self.pomodoro.remaining.is_some() || self.pomodoro.is_running())
+                .then(|| self.pomodoro.remaining(&self.config.pomodoro).as_secs()),
+        };
+
+        config::write_state(&path, &toml::to_string(&saved)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Phase::*;
+
+    #[test]
+    fn long_break_every_few_cycles() {
+        assert_eq!(Work.next(0, 4), ShortBreak);
+        assert_eq!(Work.next(1, 4), ShortBreak);
+        assert_eq!(ShortBreak.next(1, 4), Work);
+        assert_eq!(Work.next(3, 4), ShortBreak);
+        assert_eq!(Work.next(4, 4), LongBreak);
+        assert_eq!(LongBreak.next(4, 4), Work);
+        assert_eq!(Work.next(8, 4), LongBreak);
+    }
+}
@@ -1,23 +1,42 @@
 use smithay_client_toolkit::{
     compositor::CompositorHandler,
-    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_registry,
-    delegate_seat, delegate_shm,
+    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
+    delegate_registry, delegate_seat, delegate_shm, delegate_touch,
+    globals::GlobalData,
     output::{OutputHandler, OutputState},
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
     seat::{
         Capability, SeatHandler, SeatState,
         keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers},
+        pointer::{BTN_LEFT, PointerEvent, PointerEventKind, PointerHandler},
+        touch::TouchHandler,
+    },
+    shell::{
+        WaylandSurface,
+        wlr_layer::{LayerShellHandler, LayerSurface, LayerSurfaceConfigure},
     },
-    shell::wlr_layer::{LayerShellHandler, LayerSurface, LayerSurfaceConfigure},
     shm::{Shm, ShmHandler},
 };
 use wayland_client::{
-    Connection, QueueHandle,
-    protocol::{wl_keyboard, wl_output, wl_seat, wl_surface},
+    Connection, Dispatch, QueueHandle, WEnum,
+    protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_surface, wl_touch},
+};
+use wayland_protocols::ext::background_effect::v1::client::{
+    ext_background_effect_manager_v1::{self, ExtBackgroundEffectManagerV1},
+    ext_background_effect_surface_v1::{self, ExtBackgroundEffectSurfaceV1},
+};
+
+use crate::{
+    Command, Event, PomodoroAction, Sometime, State, StopwatchAction, Touch, View,
+    config::{Anchor, CloseOnClick, DragModifier},
+    keys::{Binding, KeyAction},
 };
 
-use crate::{Event, Sometime};
+// Fingers wobble, a tap may move this far in pixels
+const TAP: f64 = 16.0;
+// How far a finger must travel sideways to change the month
+const SWIPE: f64 = 48.0;
 
 impl CompositorHandler for Sometime {
     fn scale_factor_changed(
@@ -36,14 +55,17 @@ impl CompositorHandler for Sometime {
         _: wl_output::Transform,
     ) {
     }
-    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
+    fn frame(&mut self, _: &Connection, qh: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {
+        self.next_frame(qh);
+    }
     fn surface_enter(
         &mut self,
         _: &Connection,
         _: &QueueHandle<Self>,
         _: &wl_surface::WlSurface,
-        _: &wl_output::WlOutput,
+        output: &wl_output::WlOutput,
     ) {
+        self.output = self.wl.output_state.info(output).and_then(|info| info.name);
     }
     fn surface_leave(
         &mut self,
@@ -103,6 +125,24 @@ impl SeatHandler for Sometime {
                 .expect("Failed to create keyboard");
             self.wl.keyboard = Some(keyboard);
         }
+
+        if capability == Capability::Pointer && self.wl.pointer.is_none() {
+            let pointer = self
+                .wl
+                .seat_state
+                .get_pointer(qh, &seat)
+                .expect("Failed to create pointer");
+            self.wl.pointer = Some(pointer);
+        }
+
+        if capability == Capability::Touch && self.wl.touch.is_none() {
+            let touch = self
+                .wl
+                .seat_state
+                .get_touch(qh, &seat)
+                .expect("Failed to create touch");
+            self.wl.touch = Some(touch);
+        }
     }
 
     fn remove_capability(
@@ -115,6 +155,14 @@ impl SeatHandler for Sometime {
         if capability == Capability::Keyboard && self.wl.keyboard.is_some() {
             self.wl.keyboard.take().unwrap().release();
         }
+
+        if capability == Capability::Pointer && self.wl.pointer.is_some() {
+            self.wl.pointer.take().unwrap().release();
+        }
+
+        if capability == Capability::Touch && self.wl.touch.is_some() {
+            self.wl.touch.take().unwrap().release();
+        }
     }
 
     fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
@@ -128,19 +176,31 @@ impl KeyboardHandler for Sometime {
         _: &wl_keyboard::WlKeyboard,
         _: &wl_surface::WlSurface,
         _: u32,
-        _: &[u32],
+        raw: &[u32],
         _: &[Keysym],
     ) {
+        // The keys held to open a peek, told apart from modifiers next
+        if let Some(peek) = &mut self.peek {
+            peek.enter(raw);
+        }
     }
 
     fn leave(
         &mut self,
         _: &Connection,
-        _: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
         _: &wl_keyboard::WlKeyboard,
         _: &wl_surface::WlSurface,
         _: u32,
     ) {
+        // Close when focus is lost, as to a click outside or anything else
+        if matches!(
+            self.config.pointer.close_on_click,
+            CloseOnClick::Outside | CloseOnClick::Anywhere
+        ) && matches!(self.state, State::Opening(_) | State::Awake(_))
+        {
+            self.handle(Event::Hide, qh);
+        }
     }
 
     fn press_key(
@@ -151,11 +211,52 @@ impl KeyboardHandler for Sometime {
         _: u32,
         event: KeyEvent,
     ) {
-        let pressed_key = event.keysym;
+        self.reset_hide_timer(qh);
+
+        let pressed = Binding::pressed(event.keysym, self.modifiers);
+        let Some(action) = self.config.keys.action(pressed) else {
+            return;
+        };
 
-        // Close on `esc` or `q`
-        if pressed_key == Keysym::Escape || pressed_key == Keysym::q {
-            self.handle(Event::Close, qh);
+        let view = |view| Command::Switch(view);
+        let stopwatch = |action| match self.state {
+            State::Awake(View::Stopwatch) => Some(Command::Stopwatch(Some(action))),
+            _ => None,
+        };
+        let pomodoro = |action| match self.state {
+            State::Awake(View::Clock) => Some(Command::Pomodoro(action)),
+            _ => None,
+        };
+
+        let command = match action {
+            KeyAction::Close => return self.handle(Event::Hide, qh),
+            KeyAction::Clock => Some(view(View::Clock)),
+            KeyAction::Calendar => Some(view(View::Calendar)),
+            KeyAction::World => Some(view(View::World)),
+            KeyAction::Timer => Some(view(View::Timer)),
+            KeyAction::Stopwatch => Some(view(View::Stopwatch)),
+            KeyAction::NextMonth => return self.turn_month(1),
+            KeyAction::PreviousMonth => return self.turn_month(-1),
+            KeyAction::StopwatchToggle => stopwatch(StopwatchAction::Toggle),
+            KeyAction::StopwatchLap => stopwatch(StopwatchAction::Lap),
+            KeyAction::StopwatchReset => stopwatch(StopwatchAction::Reset),
+            KeyAction::TimerCancel => match (self.state, self.countdowns.first()) {
+                (State::Awake(View::Timer), Some(countdown)) => {
+                    Some(Command::CancelTimer(countdown.name.clone()))
+                }
+                _ => None,
+            },
+            KeyAction::PomodoroToggle if self.pomodoro.is_running() => {
+                pomodoro(PomodoroAction::Pause)
+            }
+            KeyAction::PomodoroToggle => pomodoro(PomodoroAction::Start),
+            KeyAction::PomodoroSkip => pomodoro(PomodoroAction::Skip),
+        };
+
+        if let Some(command) = command
+            && let Err(e) = self.command(command.into(), qh)
+        {
+            eprintln!("Key binding failed: {}", e);
         }
     }
 
@@ -175,24 +276,200 @@ impl KeyboardHandler for Sometime {
         qh: &QueueHandle<Self>,
         _: &wl_keyboard::WlKeyboard,
         _: u32,
-        _: KeyEvent,
+        event: KeyEvent,
     ) {
-        // --exit-on-release
-        if self.exit_on_release {
-            self.handle(Event::Close, qh);
+        // Close once the last key held to open a peek goes up
+        if let Some(peek) = &mut self.peek
+            && peek.release_key(event.raw_code)
+        {
+            self.handle(Event::Hide, qh);
         }
     }
 
     fn update_modifiers(
         &mut self,
         _: &Connection,
-        _: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
         _: &wl_keyboard::WlKeyboard,
         _: u32,
-        _: Modifiers,
+        modifiers: Modifiers,
         _: RawModifiers,
         _: u32,
     ) {
+        self.modifiers = modifiers;
+
+        if let Some(peek) = &mut self.peek
+            && peek.update_modifiers(modifiers)
+        {
+            self.handle(Event::Hide, qh);
+        }
+    }
+}
+
+impl PointerHandler for Sometime {
+    fn pointer_frame(
+        &mut self,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+        _: &wl_pointer::WlPointer,
+        events: &[PointerEvent],
+    ) {
+        for event in events {
+            let (x, y) = event.position;
+
+            match event.kind {
+                PointerEventKind::Press {
+                    button: BTN_LEFT, ..
+                } => {
+                    self.reset_hide_timer(qh);
+
+                    // Hold the modifier to drag the popup away from its anchor
+                    let modifier = match self.config.pointer.drag_modifier {
+                        DragModifier::Alt => self.modifiers.alt,
+                        DragModifier::Ctrl => self.modifiers.ctrl,
+                        DragModifier::Shift => self.modifiers.shift,
+                        DragModifier::Super => self.modifiers.logo,
+                    };
+                    if modifier && self.config.surface.anchor != Anchor::Center {
+                        self.drag = Some((x, y));
+                        continue;
+                    }
+
+                    self.click(x, y, qh);
+                }
+                PointerEventKind::Release {
+                    button: BTN_LEFT, ..
+                }
+                | PointerEventKind::Leave { .. } => self.drag = None,
+                PointerEventKind::Motion { .. } => {
+                    let Some((grab_x, grab_y)) = self.drag else {
+                        continue;
+                    };
+                    let (dx, dy) = ((x - grab_x).round() as i32, (y - grab_y).round() as i32);
+
+                    // The surface follows, so the grab stays under the pointer
+                    if let Some(layer) = &self.wl.layer
+                        && (dx, dy) != (0, 0)
+                    {
+                        self.config.surface.anchor.drag(&mut self.margin, dx, dy);
+                        let [top, right, bottom, left] = self.margin;
+                        layer.set_margin(top, right, bottom, left);
+                        layer.commit();
+                    }
+                }
+                PointerEventKind::Axis { vertical, .. } => {
+                    if self.state != State::Awake(View::Calendar) {
+                        continue;
+                    }
+
+                    // Wheels count in steps of 120, touchpads in pixels
+                    self.scroll += if vertical.value120 != 0 {
+                        vertical.value120 as f64 / 120.0
+                    } else if vertical.discrete != 0 {
+                        vertical.discrete as f64
+                    } else {
+                        vertical.absolute / 20.0
+                    };
+
+                    let months = self.scroll.trunc();
+                    if months != 0.0 {
+                        self.scroll -= months;
+                        self.turn_month(months as i32);
+                        self.reset_hide_timer(qh);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl TouchHandler for Sometime {
+    fn down(
+        &mut self,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+        _: &wl_touch::WlTouch,
+        _: u32,
+        _: u32,
+        _: wl_surface::WlSurface,
+        id: i32,
+        position: (f64, f64),
+    ) {
+        // Only the first finger counts
+        if self.touch.is_none() {
+            self.touch = Some(Touch {
+                id,
+                start: position,
+                last: position,
+            });
+            self.reset_hide_timer(qh);
+        }
+    }
+
+    fn up(
+        &mut self,
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+        _: &wl_touch::WlTouch,
+        _: u32,
+        _: u32,
+        id: i32,
+    ) {
+        let Some(touch) = self.touch.take_if(|touch| touch.id == id) else {
+            return;
+        };
+
+        let (x, y) = touch.start;
+        let (dx, dy) = (touch.last.0 - x, touch.last.1 - y);
+
+        if dx.abs() < TAP && dy.abs() < TAP {
+            self.tap(x, y, qh);
+        } else if dx.abs() >= SWIPE && dx.abs() > dy.abs() {
+            // Swipe left for the next month, like turning a page
+            self.turn_month(if dx < 0.0 { 1 } else { -1 });
+        }
+    }
+
+    fn motion(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_touch::WlTouch,
+        _: u32,
+        id: i32,
+        position: (f64, f64),
+    ) {
+        if let Some(touch) = self.touch.as_mut()
+            && touch.id == id
+        {
+            touch.last = position;
+        }
+    }
+
+    fn shape(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_touch::WlTouch,
+        _: i32,
+        _: f64,
+        _: f64,
+    ) {
+    }
+
+    fn orientation(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_touch::WlTouch,
+        _: i32,
+        _: f64,
+    ) {
+    }
+
+    fn cancel(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_touch::WlTouch) {
+        self.touch = None;
     }
 }
 
@@ -202,11 +479,44 @@ impl ShmHandler for Sometime {
     }
 }
 
+impl Dispatch<ExtBackgroundEffectManagerV1, GlobalData> for Sometime {
+    fn event(
+        state: &mut Self,
+        _: &ExtBackgroundEffectManagerV1,
+        event: ext_background_effect_manager_v1::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_background_effect_manager_v1::Event::Capabilities {
+            flags: WEnum::Value(flags),
+        } = event
+        {
+            state.wl.blur.capable =
+                flags.contains(ext_background_effect_manager_v1::Capability::Blur);
+        }
+    }
+}
+
+impl Dispatch<ExtBackgroundEffectSurfaceV1, ()> for Sometime {
+    fn event(
+        _: &mut Self,
+        _: &ExtBackgroundEffectSurfaceV1,
+        _: ext_background_effect_surface_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
 delegate_compositor!(Sometime);
 delegate_output!(Sometime);
 delegate_shm!(Sometime);
 delegate_seat!(Sometime);
 delegate_keyboard!(Sometime);
+delegate_pointer!(Sometime);
+delegate_touch!(Sometime);
 delegate_layer!(Sometime);
 delegate_registry!(Sometime);
 
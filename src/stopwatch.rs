@@ -0,0 +1,166 @@
+use std::time::{Duration, Instant};
+
+use crate::{Sometime, State, View, ipc::Payload};
+
+#[derive(Debug, Default)]
+pub struct Stopwatch {
+    // Set while running
+    started: Option<Instant>,
+    // Time accumulated before the last start
+    banked: Duration,
+    // Total elapsed time at each lap
+    splits: Vec<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopwatchAction {
+    Start,
+    Stop,
+    // Start or stop, for a single key
+    Toggle,
+    Lap,
+    Reset,
+}
+
+impl Stopwatch {
+    pub fn elapsed(&self) -> Duration {
+        self.banked
+            + self
+                .started
+                .map_or(Duration::ZERO, |started| started.elapsed())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    pub fn apply(&mut self, action: StopwatchAction) {
+        match action {
+            StopwatchAction::Start => self.start(),
+            StopwatchAction::Stop => self.stop(),
+            StopwatchAction::Toggle if self.is_running() => self.stop(),
+            StopwatchAction::Toggle => self.start(),
+            StopwatchAction::Lap => self.lap(),
+            StopwatchAction::Reset => *self = Self::default(),
+        }
+    }
+
+    // The most recent laps first, numbered from 1, with their own duration
+    pub fn laps(&self) -> impl Iterator<Item = (usize, Duration)> {
+        (0..self.splits.len()).rev().map(|i| {
+            let previous = i.checked_sub(1).map_or(Duration::ZERO, |j| self.splits[j]);
+            (i + 1, self.splits[i] - previous)
+        })
+    }
+
+    fn start(&mut self) {
+        self.started.get_or_insert_with(Instant::now);
+    }
+
+    fn stop(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.banked += started.elapsed();
+        }
+    }
+
+    fn lap(&mut self) {
+        if self.is_running() {
+            self.splits.push(self.elapsed());
+        }
+    }
+}
+
+impl std::str::FromStr for StopwatchAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(Self::Start),
+            "stop" => Ok(Self::Stop),
+            "toggle" => Ok(Self::Toggle),
+            "lap" => Ok(Self::Lap),
+            "reset" => Ok(Self::Reset),
+            _ => Err(format!("Unknown stopwatch action: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for StopwatchAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Start => write!(f, "start"),
+            Self::Stop => write!(f, "stop"),
+            Self::Toggle => write!(f, "toggle"),
+            Self::Lap => write!(f, "lap"),
+            Self::Reset => write!(f, "reset"),
+        }
+    }
+}
+
+impl Sometime {
+    pub(crate) fn stopwatch(&mut self, action: StopwatchAction) -> Payload {
+        self.stopwatch.apply(action);
+
+        if self.state == State::Awake(View::Stopwatch) {
+            self.draw();
+        }
+
+        Payload::Stopwatch {
+            running: self.stopwatch.is_running(),
+            elapsed_ms: self.stopwatch.elapsed().as_millis() as u64,
+            laps_ms: self
+                .stopwatch
+                .laps()
+                .map(|(_, lap)| lap.as_millis() as u64)
+                .collect(),
+        }
+    }
+}
+
+// `4:05.3` or `1:04:05.3`
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let tenths = elapsed.as_millis() / 100;
+    let secs = tenths / 10;
+    let (h, m, s) = (secs / 3600, secs % 3600 / 60, secs % 60);
+
+    if h > 0 {
+        format!("{}:{:02}:{:02}.{}", h, m, s, tenths % 10)
+    } else {
+        format!("{}:{:02}.{}", m, s, tenths % 10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Stopwatch, StopwatchAction::*, format_elapsed};
+    use std::time::Duration;
+
+    #[test]
+    fn laps_only_while_running() {
+        let mut stopwatch = Stopwatch::default();
+
+        stopwatch.apply(Lap);
+        assert_eq!(stopwatch.laps().count(), 0);
+
+        stopwatch.apply(Toggle);
+        assert!(stopwatch.is_running());
+        stopwatch.apply(Lap);
+        stopwatch.apply(Lap);
+        assert_eq!(stopwatch.laps().map(|(n, _)| n).collect::<Vec<_>>(), [2, 1]);
+
+        stopwatch.apply(Toggle);
+        assert!(!stopwatch.is_running());
+        stopwatch.apply(Lap);
+        assert_eq!(stopwatch.laps().count(), 2);
+
+        stopwatch.apply(Reset);
+        assert_eq!(stopwatch.laps().count(), 0);
+        assert_eq!(stopwatch.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn elapsed_format() {
+        assert_eq!(format_elapsed(Duration::from_millis(65_432)), "1:05.4");
+        assert_eq!(format_elapsed(Duration::from_secs(3725)), "1:02:05.0");
+    }
+}
@@ -0,0 +1,245 @@
+use smithay_client_toolkit::reexports::calloop::{
+    Interest, Mode, PostAction, RegistrationToken, generic::Generic,
+};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    os::{fd::AsRawFd, unix::net::UnixStream},
+};
+
+use crate::{
+    Sometime,
+    ipc::{Client, Notice},
+};
+
+// Bytes of notices a subscriber can fall behind by before it's dropped
+const MAX_QUEUED: usize = 64 * 1024;
+
+pub struct Subscriber {
+    outbox: Outbox,
+    // Watches the connection for the client hanging up
+    token: RegistrationToken,
+    // Watches it for room to write, while notices are queued
+    flush_token: Option<RegistrationToken>,
+}
+
+// Lines not yet taken by a subscriber, so none goes out cut short
+struct Outbox {
+    stream: UnixStream,
+    queued: Vec<u8>,
+}
+
+impl Outbox {
+    fn new(stream: UnixStream) -> Self {
+        Self {
+            stream,
+            queued: Vec::new(),
+        }
+    }
+
+    // Queue a line and write out what fits
+    fn push(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.queued.len() + line.len() > MAX_QUEUED {
+            return Err(ErrorKind::WriteZero.into());
+        }
+        self.queued.extend_from_slice(line);
+        self.flush()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.queued.is_empty() {
+            match self.stream.write(&self.queued) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.queued.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+}
+
+impl Sometime {
+    pub fn subscribe(&mut self, client: Client) -> anyhow::Result<()> {
+        let stream = client.subscribe()?;
+        let id = stream.as_raw_fd();
+
+        let watch = Generic::new(stream.try_clone()?, Interest::READ, Mode::Level);
+        let token = self
+            .loop_handle
+            .insert_source(watch, move |_, stream, app| {
+                let mut buf = [0; 64];
+                let mut stream: &UnixStream = stream;
+
+                // Whatever a subscriber sends is ignored, only EOF matters
+                match stream.read(&mut buf) {
+                    Ok(0) => {}
+                    Ok(_) => return Ok(PostAction::Continue),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        return Ok(PostAction::Continue);
+                    }
+                    Err(_) => {}
+                }
+
+                app.unsubscribe(id);
+                Ok(PostAction::Remove)
+            })
+            .map_err(|e| e.error)?;
+
+        self.subscribers.push(Subscriber {
+            outbox: Outbox::new(stream),
+            token,
+            flush_token: None,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn notify(&mut self, notice: Notice) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        let Ok(mut line) = serde_json::to_string(&notice) else {
+            return;
+        };
+        line.push('\n');
+
+        let mut dropped = Vec::new();
+        let mut waiting = Vec::new();
+        for subscriber in &mut self.subscribers {
+            let id = subscriber.outbox.stream.as_raw_fd();
+            match subscriber.outbox.push(line.as_bytes()) {
+                Err(_) => dropped.push(id),
+                Ok(()) if !subscriber.outbox.is_empty() && subscriber.flush_token.is_none() => {
+                    waiting.push(id)
+                }
+                Ok(()) => {}
+            }
+        }
+
+        for id in dropped {
+            self.unsubscribe(id);
+        }
+        for id in waiting {
+            self.flush_when_writable(id);
+        }
+    }
+
+    // Write out the rest of what's queued as the subscriber reads it
+    fn flush_when_writable(&mut self, id: i32) {
+        let Some(subscriber) = self
+            .subscribers
+            .iter_mut()
+            .find(|subscriber| subscriber.outbox.stream.as_raw_fd() == id)
+        else {
+            return;
+        };
+        let Ok(stream) = subscriber.outbox.stream.try_clone() else {
+            self.unsubscribe(id);
+            return;
+        };
+
+        let watch = Generic::new(stream, Interest::WRITE, Mode::Level);
+        let token = self.loop_handle.insert_source(watch, move |_, _, app| {
+            let Some(subscriber) = app
+                .subscribers
+                .iter_mut()
+                .find(|subscriber| subscriber.outbox.stream.as_raw_fd() == id)
+            else {
+                return Ok(PostAction::Remove);
+            };
+
+            match subscriber.outbox.flush() {
+                Ok(()) if !subscriber.outbox.is_empty() => Ok(PostAction::Continue),
+                Ok(()) => {
+                    subscriber.flush_token = None;
+                    Ok(PostAction::Remove)
+                }
+                Err(_) => {
+                    subscriber.flush_token = None;
+                    app.unsubscribe(id);
+                    Ok(PostAction::Remove)
+                }
+            }
+        });
+
+        match token {
+            Ok(token) => subscriber.flush_token = Some(token),
+            Err(_) => self.unsubscribe(id),
+        }
+    }
+
+    fn unsubscribe(&mut self, id: i32) {
+        let loop_handle = &self.loop_handle;
+        self.subscribers.retain(|subscriber| {
+            if subscriber.outbox.stream.as_raw_fd() != id {
+                return true;
+            }
+
+            loop_handle.remove(subscriber.token);
+            if let Some(token) = subscriber.flush_token {
+                loop_handle.remove(token);
+            }
+            false
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_QUEUED, Outbox};
+    use std::{
+        io::{BufRead, BufReader},
+        os::unix::net::UnixStream,
+        thread,
+        time::Duration,
+    };
+
+    #[test]
+    fn slow_subscriber_gets_whole_lines() {
+        let (cli, daemon) = UnixStream::pair().unwrap();
+        daemon.set_nonblocking(true).unwrap();
+        let mut outbox = Outbox::new(daemon);
+
+        // Until the socket is full and the rest waits
+        let line = "{\"event\":\"timer_expired\",\"name\":\"tea\"}\n";
+        let mut sent = 0;
+        while outbox.is_empty() {
+            outbox.push(line.as_bytes()).unwrap();
+            sent += 1;
+        }
+
+        let reader = thread::spawn(move || {
+            BufReader::new(cli)
+                .lines()
+                .map(|text| text.unwrap() + "\n")
+                .collect::<Vec<_>>()
+        });
+        while !outbox.is_empty() {
+            outbox.flush().unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(outbox);
+
+        let received = reader.join().unwrap();
+        assert_eq!(received.len(), sent);
+        assert!(received.iter().all(|text| text == line));
+    }
+
+    #[test]
+    fn drops_a_stalled_subscriber() {
+        let (_cli, daemon) = UnixStream::pair().unwrap();
+        daemon.set_nonblocking(true).unwrap();
+        let mut outbox = Outbox::new(daemon);
+
+        let line = [b'x'; 1024];
+        while outbox.push(&line).is_ok() {}
+        assert!(outbox.queued.len() <= MAX_QUEUED);
+    }
+}
@@ -1,11 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+use crate::composite::div255;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Bgra([u8; 4]);
 
 impl Bgra {
-    const fn from_rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+    pub(crate) const fn from_rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
         Self([blue, green, red, alpha])
     }
 
+    // The same color, `alpha` times as opaque
+    pub(crate) fn fade(self, alpha: u8) -> Self {
+        let [b, g, r, a] = self.0;
+        Self([b, g, r, div255(a as u32 * alpha as u32)])
+    }
+
     #[inline]
     pub const fn r(self) -> u8 {
         self.0[2]
@@ -24,6 +34,28 @@ impl Bgra {
     }
 }
 
+impl std::str::FromStr for Bgra {
+    type Err = String;
+
+    // `#rrggbb`, or `#rrggbbaa` with alpha
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let byte = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        };
+
+        match (hex.len(), byte(0), byte(2), byte(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Self::from_rgba(r, g, b, 255)),
+            (8, Some(r), Some(g), Some(b)) => match byte(6) {
+                Some(a) => Ok(Self::from_rgba(r, g, b, a)),
+                None => Err(format!("Invalid color: {}", s)),
+            },
+            _ => Err(format!("Invalid color: {}", s)),
+        }
+    }
+}
+
 impl AsRef<[u8]> for Bgra {
     fn as_ref(&self) -> &[u8] {
         &self.0
@@ -39,14 +71,38 @@ pub struct Theme {
     pub highlight: Bgra,
 }
 
+// The Catppuccin flavor the popup is drawn in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Mocha,
+}
+
+impl std::fmt::Display for ThemeName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mocha => write!(f, "mocha"),
+        }
+    }
+}
+
+impl From<ThemeName> for Theme {
+    fn from(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Mocha => Self {
+                background: Bgra::from_rgba(24, 24, 37, 242),   // Mantle
+                frame: Bgra::from_rgba(30, 30, 46, 42),         // Base
+                primary: Bgra::from_rgba(235, 160, 172, 242),   // Maroon
+                secondary: Bgra::from_rgba(245, 194, 231, 242), // Pink
+                highlight: Bgra::from_rgba(243, 139, 168, 242), // Red
+            },
+        }
+    }
+}
+
 impl Default for Theme {
     fn default() -> Self {
-        Self {
-            background: Bgra::from_rgba(24, 24, 37, 242),   // Mantle
-            frame: Bgra::from_rgba(30, 30, 46, 42),         // Base
-            primary: Bgra::from_rgba(235, 160, 172, 242),   // Maroon
-            secondary: Bgra::from_rgba(245, 194, 231, 242), // Pink
-            highlight: Bgra::from_rgba(243, 139, 168, 242), // Red
-        }
+        ThemeName::default().into()
     }
 }
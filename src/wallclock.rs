@@ -0,0 +1,172 @@
+use chrono_tz::Tz;
+use std::{
+    io,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+};
+
+// Becomes readable when the realtime clock is set: a manual change, an NTP
+// step or a resync after resuming from suspend
+pub struct ClockWatch {
+    fd: OwnedFd,
+}
+
+impl ClockWatch {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::timerfd_create(libc::CLOCK_REALTIME, libc::TFD_NONBLOCK | libc::TFD_CLOEXEC)
+        };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let watch = Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        };
+        watch.arm()?;
+
+        Ok(watch)
+    }
+
+    // Whether the clock jumped since the last call, re-arming the watch
+    pub fn jumped(&self) -> bool {
+        let mut expirations = 0u64;
+        let ret = unsafe {
+            libc::read(
+                self.fd.as_raw_fd(),
+                &mut expirations as *mut u64 as *mut libc::c_void,
+                size_of::<u64>(),
+            )
+        };
+
+        let jumped = ret < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::ECANCELED);
+
+        if jumped {
+            self.arm().ok();
+        }
+
+        jumped
+    }
+
+    // An absolute timer that never expires but is cancelled on every clock set
+    fn arm(&self) -> io::Result<()> {
+        let spec = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: libc::timespec {
+                tv_sec: libc::time_t::MAX / 2,
+                tv_nsec: 0,
+            },
+        };
+
+        let ret = unsafe {
+            libc::timerfd_settime(
+                self.fd.as_raw_fd(),
+                libc::TFD_TIMER_ABSTIME | libc::TFD_TIMER_CANCEL_ON_SET,
+                &spec,
+                std::ptr::null_mut(),
+            )
+        };
+
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl AsFd for ClockWatch {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+// Becomes readable when `/etc/localtime` is replaced or rewritten
+pub struct TzWatch {
+    fd: OwnedFd,
+}
+
+impl TzWatch {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // Watch the directory, `/etc/localtime` is usually a symlink that
+        // gets swapped rather than written to
+        let mask = libc::IN_CREATE
+            | libc::IN_MOVED_TO
+            | libc::IN_CLOSE_WRITE
+            | libc::IN_DELETE
+            | libc::IN_ATTRIB;
+        let ret = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), c"/etc".as_ptr(), mask) };
+
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { fd })
+    }
+
+    // Whether `localtime` showed up in the pending events, draining them
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        let mut buf = [0u8; 4096];
+        let header = size_of::<libc::inotify_event>();
+
+        loop {
+            let len = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+
+            if len <= 0 {
+                break;
+            }
+
+            let mut offset = 0;
+            while offset + header <= len as usize {
+                // `len` is the last field of the header and counts the
+                // NUL-padded name that follows it
+                let name_len_at = offset + header - size_of::<u32>();
+                let name_len =
+                    u32::from_ne_bytes(buf[name_len_at..offset + header].try_into().unwrap())
+                        as usize;
+                let name = &buf[offset + header..(offset + header + name_len).min(len as usize)];
+
+                changed |= name.split(|&b| b == 0).next() == Some(b"localtime");
+                offset += header + name_len;
+            }
+        }
+
+        changed
+    }
+}
+
+impl AsFd for TzWatch {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+// The IANA zone behind `$TZ` or `/etc/localtime`, `None` when it can't be
+// named and chrono's `Local` should be trusted instead
+pub fn local_tz() -> Option<Tz> {
+    if let Ok(tz) = std::env::var("TZ") {
+        return tz.trim_start_matches(':').parse().ok();
+    }
+
+    let target = std::fs::read_link("/etc/localtime").ok()?;
+    let (_, name) = target.to_str()?.split_once("zoneinfo/")?;
+    name.parse().ok()
+}
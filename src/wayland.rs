@@ -5,24 +5,31 @@ use smithay_client_toolkit::{
     seat::SeatState,
     shell::{
         WaylandSurface,
-        wlr_layer::{KeyboardInteractivity, Layer, LayerShell, LayerSurface},
+        wlr_layer::{self, KeyboardInteractivity, Layer, LayerShell, LayerSurface},
     },
-    shm::{Shm, slot::SlotPool},
+    shm::Shm,
+};
+use wayland_client::{
+    QueueHandle,
+    globals::GlobalList,
+    protocol::{wl_keyboard::WlKeyboard, wl_pointer::WlPointer, wl_touch::WlTouch},
 };
-use wayland_client::{QueueHandle, globals::GlobalList, protocol::wl_keyboard::WlKeyboard};
 
-use crate::{SIDE, Sometime};
+use crate::{SIDE, Sometime, blur::Blur, buffers::Buffers, config::Anchor};
 
 pub struct Wayland {
     pub registry_state: RegistryState,
     pub seat_state: SeatState,
     pub output_state: OutputState,
-    pub pool: SlotPool,
+    pub buffers: Buffers,
+    pub blur: Blur,
     pub shm: Shm,
     pub compositor: CompositorState,
     pub layer_shell: LayerShell,
     pub layer: Option<LayerSurface>,
     pub keyboard: Option<WlKeyboard>,
+    pub pointer: Option<WlPointer>,
+    pub touch: Option<WlTouch>,
     pub exit: bool,
 }
 
@@ -34,23 +41,35 @@ impl Wayland {
             registry_state: RegistryState::new(globals),
             seat_state: SeatState::new(globals, qh),
             output_state: OutputState::new(globals, qh),
-            pool: SlotPool::new((SIDE * SIDE * 4) as usize, &shm)?,
+            buffers: Buffers::new(SIDE, &shm)?,
+            blur: Blur::bind(globals, qh),
             shm,
             compositor: CompositorState::bind(globals, qh)?,
             layer_shell: LayerShell::bind(globals, qh)?,
             layer: None,
             keyboard: None,
+            pointer: None,
+            touch: None,
             exit: false,
         })
     }
 
     pub fn destroy_layer(&mut self) {
+        self.blur.detach();
         if let Some(layer) = self.layer.take() {
             layer.wl_surface().destroy();
         }
+        self.buffers.reset();
     }
 
-    pub fn create_layer(&mut self, qh: &QueueHandle<Sometime>, namespace: &str) {
+    // `margin` is top, right, bottom and left, as in `set_margin`
+    pub fn create_layer(
+        &mut self,
+        qh: &QueueHandle<Sometime>,
+        namespace: &str,
+        anchor: Anchor,
+        margin: [i32; 4],
+    ) {
         let surface = self.compositor.create_surface(qh);
         let layer = self.layer_shell.create_layer_surface(
             qh,
@@ -61,7 +80,57 @@ impl Wayland {
         );
         layer.set_keyboard_interactivity(KeyboardInteractivity::OnDemand);
         layer.set_size(SIDE as u32, SIDE as u32);
+        layer.set_anchor(anchor.edges());
+        let [top, right, bottom, left] = margin;
+        layer.set_margin(top, right, bottom, left);
+        self.blur.attach(layer.wl_surface(), qh);
         layer.commit();
         self.layer = Some(layer);
     }
 }
+
+impl Anchor {
+    fn edges(self) -> wlr_layer::Anchor {
+        use wlr_layer::Anchor as Edge;
+
+        match self {
+            Self::Center => Edge::empty(),
+            Self::Top => Edge::TOP,
+            Self::TopLeft => Edge::TOP | Edge::LEFT,
+            Self::TopRight => Edge::TOP | Edge::RIGHT,
+            Self::Bottom => Edge::BOTTOM,
+            Self::BottomLeft => Edge::BOTTOM | Edge::LEFT,
+            Self::BottomRight => Edge::BOTTOM | Edge::RIGHT,
+            Self::Left => Edge::LEFT,
+            Self::Right => Edge::RIGHT,
+        }
+    }
+
+    // The edge the popup slides in from, as a unit step in x and y
+    pub fn direction(self) -> (i32, i32) {
+        match self {
+            Self::Center | Self::Bottom | Self::BottomLeft | Self::BottomRight => (0, 1),
+            Self::Top | Self::TopLeft | Self::TopRight => (0, -1),
+            Self::Left => (-1, 0),
+            Self::Right => (1, 0),
+        }
+    }
+
+    // How a drag by `dx`, `dy` changes the top, right, bottom and left margins
+    pub fn drag(self, margin: &mut [i32; 4], dx: i32, dy: i32) {
+        let edges = self.edges();
+
+        if edges.contains(wlr_layer::Anchor::TOP) {
+            margin[0] += dy;
+        }
+        if edges.contains(wlr_layer::Anchor::RIGHT) {
+            margin[1] -= dx;
+        }
+        if edges.contains(wlr_layer::Anchor::BOTTOM) {
+            margin[2] -= dy;
+        }
+        if edges.contains(wlr_layer::Anchor::LEFT) {
+            margin[3] += dx;
+        }
+    }
+}